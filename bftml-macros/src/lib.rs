@@ -0,0 +1,194 @@
+//! `#[derive(Element)]`, which turns a generic tag into a struct. See `elements` in the main crate for how fields are read.
+//! The generated code names paths within the main crate, so the derive only works there.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as Tokens;
+use quote::quote;
+use syn::{ext::IdentExt, parse_macro_input, spanned::Spanned, Attribute, Data, DeriveInput, Error, Fields, GenericArgument, Ident, LitInt, LitStr, PathArguments, Result, Type};
+
+#[proc_macro_derive(Element, attributes(element))]
+pub fn derive_element(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+fn expand(input: &DeriveInput) -> Result<Tokens> {
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new(input.ident.span(), "elements can only be derived for structs"));
+    };
+    let fields = match &data.fields {
+        Fields::Named(fields) => fields.named.iter().collect(),
+        Fields::Unit => Vec::new(),
+        Fields::Unnamed(_) => return Err(Error::new(data.fields.span(), "elements need named fields")),
+    };
+
+    let mut name = None;
+    for attr in element_attrs(&input.attrs) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                name = Some(meta.value()?.parse::<LitStr>()?.value());
+                Ok(())
+            } else {
+                Err(meta.error("expected `name`"))
+            }
+        })?;
+    }
+    let name = name.unwrap_or_else(|| kebab(&input.ident.to_string()));
+
+    let mut reads = Vec::new();
+    let mut inits = Vec::new();
+    let mut inner = None;
+    for field in fields {
+        let ident = field.ident.as_ref().expect("named fields have names");
+        let options = Options::parse(field)?;
+        let key = options.name.clone().unwrap_or_else(|| ident.unraw().to_string());
+        let ty = &field.ty;
+
+        let (read, init) = match options.kind {
+            Kind::Preset => {
+                if !is(ty, "bool") {
+                    return Err(Error::new(ty.span(), "presets are `bool` fields"));
+                }
+                (quote!(check.preset(#key)), quote!(#ident))
+            }
+            Kind::Attribute => match wrapped(ty, "Option") {
+                Some(inner) => (quote!(check.attribute::<#inner>(#key)), quote!(#ident)),
+                None => (quote!(check.required::<#ty>(#key)), required(ident)),
+            },
+            Kind::Children { min, max } => {
+                if let Some(previous) = inner.replace(ident) {
+                    return Err(Error::new(ident.span(), format!("`{previous}` already takes the inner of the tag")));
+                }
+                let bounds = min.is_some() || max.is_some();
+                match (wrapped(ty, "Vec"), wrapped(ty, "Option")) {
+                    (Some(child), _) => {
+                        let min = min.unwrap_or(0);
+                        let max = match max {
+                            Some(max) => quote!(::std::option::Option::Some(#max)),
+                            None => quote!(::std::option::Option::None),
+                        };
+                        (quote!(check.children::<#child>(#min, #max)), quote!(#ident))
+                    }
+                    _ if bounds => return Err(Error::new(ty.span(), "only `Vec` children take `min` and `max`")),
+                    (None, Some(child)) => (quote!(check.children::<#child>(0, ::std::option::Option::Some(1)).pop()), quote!(#ident)),
+                    (None, None) => (quote!(check.children::<#ty>(1, ::std::option::Option::Some(1)).pop()), required(ident)),
+                }
+            }
+            Kind::Content => {
+                if let Some(previous) = inner.replace(ident) {
+                    return Err(Error::new(ident.span(), format!("`{previous}` already takes the inner of the tag")));
+                }
+                if !is(ty, "String") {
+                    return Err(Error::new(ty.span(), "content goes in a `String` field"));
+                }
+                (quote!(check.content().to_owned()), quote!(#ident))
+            }
+        };
+        reads.push(quote!(let #ident = #read;));
+        inits.push(quote!(#ident: #init));
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics crate::elements::Element for #ident #ty_generics #where_clause {
+            const NAME: &'static str = #name;
+
+            fn from_tag(tag: &crate::parser::tags::Tag) -> ::std::result::Result<Self, ::std::vec::Vec<crate::elements::TagError>> {
+                let mut check = crate::elements::Check::new(tag, Self::NAME);
+                #(#reads)*
+                check.finish()?;
+                ::std::result::Result::Ok(Self { #(#inits),* })
+            }
+        }
+    })
+}
+
+/// Only reached once the check has passed, which means every required field was found
+fn required(ident: &Ident) -> Tokens {
+    quote!(#ident.expect("checked to be present"))
+}
+
+enum Kind {
+    Attribute,
+    Preset,
+    Children { min: Option<usize>, max: Option<usize> },
+    Content,
+}
+
+/// What `#[element(...)]` says about a field
+struct Options {
+    kind: Kind,
+    name: Option<String>,
+}
+
+impl Options {
+    fn parse(field: &syn::Field) -> Result<Self> {
+        let mut options = Options { kind: Kind::Attribute, name: None };
+        let (mut min, mut max) = (None, None);
+        for attr in element_attrs(&field.attrs) {
+            attr.parse_nested_meta(|meta| {
+                let path = &meta.path;
+                if path.is_ident("preset") {
+                    options.kind = Kind::Preset;
+                } else if path.is_ident("children") {
+                    options.kind = Kind::Children { min: None, max: None };
+                } else if path.is_ident("content") {
+                    options.kind = Kind::Content;
+                } else if path.is_ident("name") {
+                    options.name = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if path.is_ident("min") {
+                    min = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+                } else if path.is_ident("max") {
+                    max = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+                } else {
+                    return Err(meta.error("expected `preset`, `children`, `content`, `name`, `min` or `max`"));
+                }
+                Ok(())
+            })?;
+        }
+        match &mut options.kind {
+            Kind::Children { min: lower, max: upper } => (*lower, *upper) = (min, max),
+            _ if min.is_some() || max.is_some() => return Err(Error::new(field.span(), "only children take `min` and `max`")),
+            _ => {}
+        }
+        if options.name.is_some() && matches!(options.kind, Kind::Children { .. } | Kind::Content) {
+            return Err(Error::new(field.span(), "only presets and attributes have names"));
+        }
+        Ok(options)
+    }
+}
+
+fn element_attrs(attrs: &[Attribute]) -> impl Iterator<Item = &Attribute> {
+    attrs.iter().filter(|attr| attr.path().is_ident("element"))
+}
+
+/// The type argument of `ty` if it is `wrapper<T>`, going by the last segment of its path
+fn wrapped<'t>(ty: &'t Type, wrapper: &str) -> Option<&'t Type> {
+    let Type::Path(path) = ty else { return None };
+    let segment = path.path.segments.last()?;
+    if segment.ident != wrapper {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else { return None };
+    match args.args.first()? {
+        GenericArgument::Type(inner) => Some(inner),
+        _ => None,
+    }
+}
+
+fn is(ty: &Type, name: &str) -> bool {
+    matches!(ty, Type::Path(path) if path.path.segments.last().is_some_and(|segment| segment.ident == name))
+}
+
+/// `SpacerBox` as `spacer-box`
+fn kebab(name: &str) -> String {
+    let mut kebab = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            kebab.push('-');
+        }
+        kebab.extend(c.to_lowercase());
+    }
+    kebab
+}
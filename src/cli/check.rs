@@ -0,0 +1,76 @@
+use std::{fs, path::PathBuf};
+
+use crate::{elements::schema::{LoadError, Schema, SchemaError}, parser::{document::Document, error::Diagnostic}};
+
+const USAGE: &str = "usage: bftml check --schema <schema> <paths>...";
+
+/// Checks each file against a schema, reporting every tag error found
+pub fn run(args: &[String]) -> i32 {
+    let mut schema = None;
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--schema" {
+            let Some(path) = args.next() else {
+                eprintln!("error: `--schema` needs a path\n\n{USAGE}");
+                return 2;
+            };
+            schema = Some(PathBuf::from(path));
+        } else {
+            paths.push(PathBuf::from(arg));
+        }
+    }
+    let (Some(schema), false) = (schema, paths.is_empty()) else {
+        eprintln!("error: a schema and at least one path are needed\n\n{USAGE}");
+        return 2;
+    };
+
+    let schema = match Schema::from_path(&schema) {
+        Ok(schema) => schema,
+        Err(LoadError::Io { path, error }) => {
+            eprintln!("error: could not read {}: {error}", path.display());
+            return 1;
+        }
+        Err(LoadError::Invalid { path, text, error }) => {
+            let name = path.display().to_string();
+            match error {
+                SchemaError::Syntax(error) => eprintln!("{}", error.report(&text).with_path(&name)),
+                SchemaError::Tags(errors) => errors.iter().for_each(|error| eprintln!("{}", error.report(&text).with_path(&name))),
+            }
+            return 1;
+        }
+    };
+    let files = match super::files(&paths) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return 1;
+        }
+    };
+
+    let mut failed = false;
+    for path in files {
+        let name = path.display().to_string();
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) => {
+                eprintln!("error: could not read {name}: {e}");
+                failed = true;
+                continue;
+            }
+        };
+        let document: Document = match text.parse() {
+            Ok(document) => document,
+            Err(e) => {
+                eprintln!("{}", e.report(&text).with_path(&name));
+                failed = true;
+                continue;
+            }
+        };
+        for error in schema.validate_document(&document) {
+            eprintln!("{}", error.report(&text).with_path(&name));
+            failed = true;
+        }
+    }
+    i32::from(failed)
+}
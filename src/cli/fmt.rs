@@ -0,0 +1,107 @@
+use std::{fs, path::PathBuf};
+
+use winnow::Parser;
+
+use crate::parser::{document::document, error::{self, Diagnostic, SyntaxError}, printer::Printer, span::Source};
+
+/// Rewrites each file in the canonical layout, or with `--check` only reports the ones that are not
+pub fn run(args: &[String]) -> i32 {
+    let check = args.iter().any(|arg| arg == "--check");
+    let paths: Vec<PathBuf> = args.iter().filter(|arg| *arg != "--check").map(PathBuf::from).collect();
+    if paths.is_empty() {
+        eprintln!("error: no paths given\n\nusage: bftml fmt [--check] <paths>...");
+        return 2;
+    }
+    let files = match super::files(&paths) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return 1;
+        }
+    };
+
+    let mut failed = false;
+    for path in files {
+        let name = path.display().to_string();
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) => {
+                eprintln!("error: could not read {name}: {e}");
+                failed = true;
+                continue;
+            }
+        };
+        let formatted = match format(&text) {
+            Ok(formatted) => formatted,
+            Err(e) => {
+                eprintln!("{}", e.report(&text).with_path(&name));
+                failed = true;
+                continue;
+            }
+        };
+        if formatted == text {
+            continue;
+        }
+        if check {
+            println!("would reformat {name}");
+            failed = true;
+        } else if let Err(e) = fs::write(&path, formatted) {
+            eprintln!("error: could not write {name}: {e}");
+            failed = true;
+        }
+    }
+    i32::from(failed)
+}
+
+/// The canonical layout of a file, keeping its comments
+pub fn format(text: &str) -> Result<String, SyntaxError> {
+    let source = Source::new(text).keeping_comments();
+    let document = document.parse_next(&mut source.input()).map_err(error::finish)?;
+    Ok(Printer::default().document(&document))
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::{format, run};
+
+    #[test]
+    fn test_format() {
+        let text = "<!-- counter -->\n<column   default  gap=4lp>\n<!-- buttons -->\n\n\t<button label=\"+\"/><text>Count: <!-- n -->{n}</text></column>";
+        let expected = "\
+<!-- counter -->
+<column default gap=4lp>
+    <!-- buttons -->
+    <button label=\"+\" />
+    <text>Count: <!-- n -->{n}</text>
+</column>
+";
+        assert_eq!(expected, format(text).unwrap());
+        assert_eq!(expected, format(expected).unwrap());
+        assert!(format("<column>").is_err());
+    }
+
+    #[test]
+    fn test_run() {
+        let dir = std::env::temp_dir().join(format!("bftml-fmt-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (formatted, messy) = (dir.join("formatted.bftml"), dir.join("messy.bftml"));
+        fs::write(&formatted, "<column>\n    <spacer />\n</column>\n").unwrap();
+        fs::write(&messy, "<column><spacer/></column>").unwrap();
+        let args = |args: &[&std::path::Path]| -> Vec<String> { args.iter().map(|arg| arg.display().to_string()).collect() };
+
+        assert_eq!(0, run(&args(&["--check".as_ref(), &formatted])));
+        // Only reported with `--check`, and left as it is
+        assert_eq!(1, run(&args(&["--check".as_ref(), &formatted, &messy])));
+        assert_eq!("<column><spacer/></column>", fs::read_to_string(&messy).unwrap());
+        assert_eq!(0, run(&args(&[&dir])));
+        assert_eq!(fs::read_to_string(&formatted).unwrap(), fs::read_to_string(&messy).unwrap());
+        assert_eq!(0, run(&args(&["--check".as_ref(), &dir])));
+
+        assert_eq!(2, run(&args(&["--check".as_ref()])));
+        fs::write(&messy, "<column>").unwrap();
+        assert_eq!(1, run(&args(&["--check".as_ref(), &messy])));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
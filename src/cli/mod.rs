@@ -0,0 +1,73 @@
+//! Commands run as `bftml <command>` rather than opening a window
+
+use std::{fs, io, path::{Path, PathBuf}};
+
+mod check;
+mod fmt;
+
+const USAGE: &str = "\
+usage: bftml [command]
+
+Without a command, opens the window.
+
+commands:
+    fmt [--check] <paths>...             Rewrite files in the canonical layout
+    check --schema <schema> <paths>...   Check files against the tags a schema allows";
+
+/// Runs the command named by `args`, giving back the exit code
+pub fn run(args: &[String]) -> i32 {
+    match args.split_first() {
+        Some((command, rest)) if command == "fmt" => fmt::run(rest),
+        Some((command, rest)) if command == "check" => check::run(rest),
+        Some((command, _)) if command == "-h" || command == "--help" => {
+            println!("{USAGE}");
+            0
+        }
+        Some((command, _)) => {
+            eprintln!("error: unknown command `{command}`\n\n{USAGE}");
+            2
+        }
+        None => {
+            eprintln!("{USAGE}");
+            2
+        }
+    }
+}
+
+/// Markup files at the given paths, looking through directories for `.bftml` and `.xml` files
+fn files(paths: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let mut entries: Vec<PathBuf> = fs::read_dir(path)?.map(|e| e.map(|e| e.path())).collect::<io::Result<_>>()?;
+            entries.sort();
+            let entries: Vec<PathBuf> = entries.into_iter().filter(|entry| entry.is_dir() || is_markup(entry)).collect();
+            files.extend(self::files(&entries)?);
+        } else {
+            files.push(path.clone());
+        }
+    }
+    Ok(files)
+}
+
+fn is_markup(path: &Path) -> bool {
+    matches!(path.extension().and_then(|e| e.to_str()), Some("bftml" | "xml"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::run;
+
+    #[test]
+    fn test_run() {
+        let args = |args: &[&str]| -> Vec<String> { args.iter().map(|arg| arg.to_string()).collect() };
+        assert_eq!(2, run(&args(&["lint", "file.xml"])));
+        // Flags of a command are not commands
+        assert_eq!(2, run(&args(&["--check", "file.xml"])));
+        assert_eq!(2, run(&[]));
+        assert_eq!(0, run(&args(&["--help"])));
+        // Known commands check their own arguments
+        assert_eq!(2, run(&args(&["fmt", "--check"])));
+        assert_eq!(2, run(&args(&["check", "file.xml"])));
+    }
+}
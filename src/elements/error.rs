@@ -0,0 +1,89 @@
+use std::fmt;
+
+use crate::parser::{error::{Diagnostic, Label}, span::{Span, Spanned}};
+
+/// Errors about what a particular tag permits, found once the syntax is known to be fine
+#[derive(Clone, Debug, PartialEq)]
+pub enum TagError {
+    WrongTag { expected: String, found: String, span: Span },
+    /// A tag the [`Schema`](super::schema::Schema) has nothing on
+    UnknownTag { name: String, span: Span },
+    UnknownPreset { tag: String, name: Spanned<String> },
+    UnknownAttribute { tag: String, key: Spanned<String> },
+    MissingAttribute { tag: String, key: String, span: Span },
+    /// `found` is the kind of value given, in the words of [`Value::kind`](crate::parser::values::Value::kind)
+    WrongType { key: String, expected: &'static str, found: &'static str, span: Span },
+    /// Where `max` is `None`, any number of children from `min` up is fine
+    ChildCount { tag: String, min: usize, max: Option<usize>, found: usize, span: Span },
+    UnexpectedContent { tag: String, span: Span },
+    /// A schema defining the same tag, or the same attribute or preset of a tag, more than once
+    Duplicate { kind: &'static str, name: String, first: Span, second: Span },
+}
+
+impl TagError {
+    pub fn span(&self) -> Span {
+        match self {
+            TagError::WrongTag { span, .. }
+            | TagError::UnknownTag { span, .. }
+            | TagError::MissingAttribute { span, .. }
+            | TagError::WrongType { span, .. }
+            | TagError::ChildCount { span, .. }
+            | TagError::UnexpectedContent { span, .. } => *span,
+            TagError::UnknownPreset { name, .. } => name.span,
+            TagError::UnknownAttribute { key, .. } => key.span,
+            TagError::Duplicate { second, .. } => *second,
+        }
+    }
+}
+
+/// Counts as the tag would have them written, as in "exactly 1 child" or "at least 2 children"
+fn children(min: usize, max: Option<usize>) -> String {
+    let plural = |n: usize| if n == 1 { format!("{n} child") } else { format!("{n} children") };
+    match max {
+        Some(0) => "no children".into(),
+        Some(max) if max == min => format!("exactly {}", plural(max)),
+        Some(max) if min == 0 => format!("at most {}", plural(max)),
+        Some(max) => format!("{min} to {max} children"),
+        None => format!("at least {}", plural(min)),
+    }
+}
+
+impl Diagnostic for TagError {
+    fn message(&self) -> String {
+        match self {
+            TagError::WrongTag { expected, found, .. } => format!("expected `<{expected}>`, found `<{found}>`"),
+            TagError::UnknownTag { name, .. } => format!("`<{name}>` is not a known tag"),
+            TagError::UnknownPreset { tag, name } => format!("`<{tag}>` has no preset `{}`", name.value),
+            TagError::UnknownAttribute { tag, key } => format!("`<{tag}>` has no attribute `{}`", key.value),
+            TagError::MissingAttribute { tag, key, .. } => format!("`<{tag}>` is missing the attribute `{key}`"),
+            TagError::WrongType { key, expected, .. } => format!("attribute `{key}` should be {expected}"),
+            TagError::ChildCount { tag, min, max, .. } => format!("`<{tag}>` takes {}", children(*min, *max)),
+            TagError::UnexpectedContent { tag, .. } => format!("`<{tag}>` does not take content"),
+            TagError::Duplicate { kind, name, .. } => format!("{kind} `{name}` defined more than once"),
+        }
+    }
+
+    fn labels(&self) -> Vec<Label> {
+        let primary = |span, message: String| Label { span, message, primary: true };
+        if let TagError::Duplicate { first, second, .. } = self {
+            return vec![Label { span: *first, message: "first defined here".into(), primary: false }, primary(*second, "defined again here".into())];
+        }
+        vec![match self {
+            TagError::WrongTag { .. } => primary(self.span(), String::new()),
+            TagError::UnknownTag { .. } | TagError::UnknownPreset { .. } | TagError::UnknownAttribute { .. } => primary(self.span(), "not known here".into()),
+            TagError::MissingAttribute { key, span, .. } => primary(*span, format!("`{key}` is required")),
+            TagError::WrongType { found, span, .. } => primary(*span, format!("found {found}")),
+            TagError::ChildCount { found, span, .. } => primary(*span, format!("found {found}")),
+            TagError::UnexpectedContent { span, .. } => primary(*span, String::new()),
+            TagError::Duplicate { .. } => unreachable!("Labelled above"),
+        }]
+    }
+}
+
+impl fmt::Display for TagError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.message(), self.span())
+    }
+}
+
+impl std::error::Error for TagError {}
@@ -0,0 +1,294 @@
+//! The second stage of parsing: checking a generic [`Tag`] against what a particular tag permits,
+//! and turning it into a concrete element. Problems found here are [`TagError`]s rather than syntax errors.
+//!
+//! Elements are usually derived, as in
+//! ```ignore
+//! #[derive(Element)]
+//! struct Button {
+//!     #[element(preset)]
+//!     default: bool,
+//!     label: String,
+//!     color: Option<Color>,
+//!     #[element(content)]
+//!     text: String,
+//! }
+//! ```
+//! Each field is an attribute by default, which is required unless its type is an `Option`.
+//! `#[element(preset)]` makes a `bool` field a preset instead, and `#[element(name = "...")]` names
+//! the preset or attribute something other than the field. A field marked `#[element(children)]` takes
+//! the children as any type implementing [`Child`]: a `Vec` for any number of them, optionally limited
+//! with `min` and `max`, an `Option` for at most one, or anything else for exactly one.
+//! A `String` field marked `#[element(content)]` takes the content.
+//! The tag is named after the struct in kebab case, unless given as `#[element(name = "...")]` on the struct.
+
+use crate::parser::{span::Spanned, tags::{Inner, Node, Tag, PRESERVE}, values::{FromValue, Value}};
+
+pub use bftml_macros::Element;
+pub use error::TagError;
+
+mod error;
+pub mod presets;
+pub mod schema;
+
+pub trait Element: Sized {
+    /// The name of the tag this element is written as
+    const NAME: &'static str;
+
+    /// Checks the tag, giving back every error found rather than just the first
+    fn from_tag(tag: &Tag) -> Result<Self, Vec<TagError>>;
+}
+
+/// What children can be taken as: any tag as it is, or an element
+pub trait Child: Sized {
+    fn from_child(tag: &Tag) -> Result<Self, Vec<TagError>>;
+}
+
+impl Child for Tag {
+    fn from_child(tag: &Tag) -> Result<Self, Vec<TagError>> {
+        Ok(tag.clone())
+    }
+}
+
+impl<E: Element> Child for E {
+    fn from_child(tag: &Tag) -> Result<Self, Vec<TagError>> {
+        E::from_tag(tag)
+    }
+}
+
+/// Typed access to a tag, for code reading it by hand rather than through an [`Element`]
+impl Tag {
+    /// The attribute as `T`, or `None` when it is not given at all
+    pub fn get<T: FromValue>(&self, key: &str) -> Result<Option<T>, TagError> {
+        let Some(value) = self.attributes.get(key) else { return Ok(None) };
+        match T::from_value(value) {
+            Some(converted) => Ok(Some(converted)),
+            None => Err(TagError::WrongType { key: key.into(), expected: T::EXPECTED, found: value.kind(), span: value.span }),
+        }
+    }
+
+    pub fn require<T: FromValue>(&self, key: &str) -> Result<T, TagError> {
+        self.get(key)?.ok_or_else(|| TagError::MissingAttribute { tag: self.name.value.clone(), key: key.into(), span: self.name.span })
+    }
+
+    pub fn has_preset(&self, name: &str) -> bool {
+        self.traits.contains(name)
+    }
+}
+
+/// Checks a tag one part at a time, collecting every error on the way, for the code `#[derive(Element)]` generates
+/// and for [`Schema`](schema::Schema)s. Anything not asked for is an error once the check is finished.
+pub struct Check<'t> {
+    tag: &'t Tag,
+    errors: Vec<TagError>,
+    presets: Vec<&'t str>,
+    attributes: Vec<&'t str>,
+    children: bool,
+    content: bool,
+}
+
+impl<'t> Check<'t> {
+    pub fn new(tag: &'t Tag, name: &str) -> Self {
+        let mut errors = Vec::new();
+        if tag.name != name {
+            errors.push(TagError::WrongTag { expected: name.into(), found: tag.name.value.clone(), span: tag.name.span });
+        }
+        Check { tag, errors, presets: Vec::new(), attributes: Vec::new(), children: false, content: false }
+    }
+
+    pub fn preset(&mut self, name: &'t str) -> bool {
+        self.presets.push(name);
+        self.tag.has_preset(name)
+    }
+
+    /// Marks `key` as known, giving back its value if it was given
+    pub fn value(&mut self, key: &'t str) -> Option<&'t Spanned<Value>> {
+        self.attributes.push(key);
+        self.tag.attributes.get(key)
+    }
+
+    pub fn wrong_type(&mut self, key: &str, expected: &'static str, value: &Spanned<Value>) {
+        self.errors.push(TagError::WrongType { key: key.into(), expected, found: value.kind(), span: value.span });
+    }
+
+    pub fn missing(&mut self, key: &str) {
+        self.errors.push(TagError::MissingAttribute { tag: self.tag.name.value.clone(), key: key.into(), span: self.tag.name.span });
+    }
+
+    /// An attribute that may be left out
+    pub fn attribute<T: FromValue>(&mut self, key: &'t str) -> Option<T> {
+        self.attributes.push(key);
+        self.tag.get(key).unwrap_or_else(|error| {
+            self.errors.push(error);
+            None
+        })
+    }
+
+    pub fn required<T: FromValue>(&mut self, key: &'t str) -> Option<T> {
+        self.attributes.push(key);
+        self.tag.require(key).map_err(|error| self.errors.push(error)).ok()
+    }
+
+    /// Every child tag, leaving out comments and any markup that failed to parse
+    pub fn count(&mut self, min: usize, max: Option<usize>) -> Vec<&'t Tag> {
+        self.children = true;
+        let tags: Vec<&Tag> = match &self.tag.inner {
+            Inner::Children(children) => children.iter().filter_map(|node| match node {
+                Node::Tag(tag) => Some(tag),
+                _ => None,
+            }).collect(),
+            _ => Vec::new(),
+        };
+        if tags.len() < min || max.is_some_and(|max| tags.len() > max) {
+            self.errors.push(TagError::ChildCount { tag: self.tag.name.value.clone(), min, max, found: tags.len(), span: self.tag.span });
+        }
+        tags
+    }
+
+    pub fn children<T: Child>(&mut self, min: usize, max: Option<usize>) -> Vec<T> {
+        let tags = self.count(min, max);
+        tags.into_iter().filter_map(|tag| T::from_child(tag).map_err(|errors| self.errors.extend(errors)).ok()).collect()
+    }
+
+    pub fn content(&mut self) -> &'t str {
+        self.content = true;
+        match &self.tag.inner {
+            Inner::Content(text) => &text.value,
+            _ => "",
+        }
+    }
+
+    pub fn finish(mut self) -> Result<(), Vec<TagError>> {
+        let tag = &self.tag.name.value;
+        for name in &self.tag.traits {
+            // Whitespace is up to any tag taking content
+            let known = self.presets.contains(&name.as_str()) || (self.content && name.value == PRESERVE);
+            if !known {
+                self.errors.push(TagError::UnknownPreset { tag: tag.clone(), name: name.clone() });
+            }
+        }
+        for key in self.tag.attributes.keys() {
+            if !self.attributes.contains(&key.as_str()) {
+                self.errors.push(TagError::UnknownAttribute { tag: tag.clone(), key: key.clone() });
+            }
+        }
+        match &self.tag.inner {
+            Inner::Children(children) if !self.children && !children.is_empty() => {
+                let found = children.iter().filter(|node| matches!(node, Node::Tag(_))).count();
+                let span = children.first().map_or(self.tag.span, Node::span);
+                self.errors.push(TagError::ChildCount { tag: tag.clone(), min: 0, max: Some(0), found, span });
+            }
+            Inner::Content(text) if !self.content && !text.is_empty() => {
+                self.errors.push(TagError::UnexpectedContent { tag: tag.clone(), span: text.span });
+            }
+            _ => {}
+        }
+        if self.errors.is_empty() {
+            return Ok(());
+        }
+        self.errors.sort_by_key(|error| error.span().start.offset);
+        Err(self.errors)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parser::{color::Color, error::Diagnostic, length::Length, tags::Tag, values::Range};
+
+    use super::{Element, TagError};
+
+    #[derive(Debug, Element, PartialEq)]
+    struct Button {
+        #[element(preset)]
+        default: bool,
+        label: String,
+        color: Option<Color>,
+        #[element(name = "min-width")]
+        min_width: Option<Length>,
+        #[element(content)]
+        text: String,
+    }
+
+    #[derive(Debug, Element, PartialEq)]
+    #[element(name = "column")]
+    struct Stack {
+        gap: f64,
+        #[element(children, min = 1)]
+        buttons: Vec<Button>,
+    }
+
+    #[derive(Debug, Element, PartialEq)]
+    struct SpacerBox {
+        #[element(children)]
+        inner: Option<Tag>,
+    }
+
+    fn check<E: Element>(text: &str) -> Result<E, Vec<TagError>> {
+        E::from_tag(&text.parse().unwrap())
+    }
+
+    #[test]
+    fn test_derive() {
+        let button: Button = check("<button default label=\"+\" min-width=40lp>Add</button>").unwrap();
+        let expected = Button { default: true, label: "+".into(), color: None, min_width: Some(Length::Lp(40.0)), text: "Add".into() };
+        assert_eq!(expected, button);
+
+        let stack: Stack = check("<column gap=4><button label=\"a\" /><button label=\"b\" color=red /></column>").unwrap();
+        assert_eq!(4.0, stack.gap);
+        assert_eq!(Some(Color::rgb(255, 0, 0)), stack.buttons[1].color);
+
+        assert_eq!("spacer-box", SpacerBox::NAME);
+        assert!(check::<SpacerBox>("<spacer-box />").unwrap().inner.is_none());
+        assert_eq!("a", check::<SpacerBox>("<spacer-box><a /></spacer-box>").unwrap().inner.unwrap().name);
+    }
+
+    #[test]
+    fn test_tag_errors() {
+        let errors = check::<Button>("<button rounded label=1 size=2>Add</button>").unwrap_err();
+        assert!(matches!(&errors[..], [
+            TagError::UnknownPreset { .. },
+            TagError::WrongType { expected: "a string", found: "an integer", .. },
+            TagError::UnknownAttribute { .. },
+        ]), "{errors:?}");
+        assert_eq!("attribute `label` should be a string", errors[1].message());
+
+        let errors = check::<Button>("<button><a /></button>").unwrap_err();
+        assert!(matches!(&errors[..], [TagError::MissingAttribute { .. }, TagError::ChildCount { max: Some(0), found: 1, .. }]), "{errors:?}");
+        assert!(matches!(&check::<Button>("<text label=\"\" />").unwrap_err()[..], [TagError::WrongTag { .. }]));
+        assert!(check::<Button>("<button preserve label=\"\"> a </button>").is_ok());
+
+        // Errors within children come back along with the parent's own
+        let errors = check::<Stack>("<column gap=[1]><button /></column>").unwrap_err();
+        assert!(matches!(&errors[..], [TagError::WrongType { .. }, TagError::MissingAttribute { .. }]), "{errors:?}");
+        let errors = check::<Stack>("<column gap=1 />").unwrap_err();
+        assert!(matches!(&errors[..], [TagError::ChildCount { min: 1, max: None, found: 0, .. }]));
+        assert_eq!("`<column>` takes at least 1 child", errors[0].message());
+        assert!(matches!(&check::<Stack>("<column gap=1>Text</column>").unwrap_err()[..], [TagError::ChildCount { .. }, TagError::UnexpectedContent { .. }]));
+        let errors = check::<SpacerBox>("<spacer-box><a /><b /></spacer-box>").unwrap_err();
+        assert_eq!("`<spacer-box>` takes at most 1 child", errors[0].message());
+    }
+
+    #[test]
+    fn test_accessors() {
+        let text = "<slider vertical value=0.5 steps=10 range=0..1 color=#ff0000 width=4lp label=\"Volume\" />";
+        let tag: Tag = text.parse().unwrap();
+        assert!(tag.has_preset("vertical"));
+        assert!(!tag.has_preset("disabled"));
+        assert_eq!(Some(0.5), tag.get::<f64>("value").unwrap());
+        // Integers will do for floats
+        assert_eq!(10.0, tag.require::<f32>("steps").unwrap());
+        assert_eq!(10, tag.require::<i64>("steps").unwrap());
+        assert_eq!(Range::from(0..1), tag.require::<Range<i64>>("range").unwrap());
+        assert_eq!(Color::rgb(255, 0, 0), tag.require::<Color>("color").unwrap());
+        assert_eq!(Length::Lp(4.0), tag.require::<Length>("width").unwrap());
+        assert_eq!("Volume", tag.require::<String>("label").unwrap());
+        assert_eq!(None, tag.get::<bool>("checked").unwrap());
+
+        let error = tag.get::<i64>("value").unwrap_err();
+        assert!(matches!(error, TagError::WrongType { expected: "an integer", found: "a float", .. }), "{error:?}");
+        assert_eq!("0.5", &text[error.span().bytes()]);
+        let error = tag.require::<bool>("checked").unwrap_err();
+        assert!(matches!(&error, TagError::MissingAttribute { key, .. } if key == "checked"));
+        // Pointing at the name of the tag missing it
+        assert_eq!("slider", &text[error.span().bytes()]);
+    }
+}
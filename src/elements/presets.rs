@@ -0,0 +1,267 @@
+//! Presets as bundles of attributes, given to every tag that names them.
+//! A preset is defined in markup, with a bundle for each type of tag it applies to:
+//! ```xml
+//! <preset name="primary">
+//!     <button color=#3366ff />
+//!     <text color=#ffffff weight=700 />
+//! </preset>
+//! ```
+//! Definitions can sit at the root of a document alongside everything else, or in a file of their own.
+//! A [`Schema`] can define them too, with a `<preset>` inside a `<tag>`.
+//!
+//! Expanding a tree gives each tag the attributes of its presets. An attribute written on the tag itself
+//! always wins over a preset, and a later preset on the tag wins over an earlier one.
+
+use indexmap::IndexMap;
+
+use crate::parser::{document::Document, incremental::Path, span::{Span, Spanned}, tags::{Inner, Node, Tag}, values::Value};
+
+use super::{schema::Schema, Check, TagError};
+
+/// The attributes a preset gives one type of tag
+pub type Bundle = IndexMap<Spanned<String>, Spanned<Value>>;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Presets {
+    /// Each preset, with a bundle for each type of tag it applies to
+    pub presets: IndexMap<String, IndexMap<String, Bundle>>,
+}
+
+/// Where an attribute of an expanded tag came from
+#[derive(Clone, Debug, PartialEq)]
+pub struct Origin {
+    /// The preset as named on the tag, along with where its definition gives the value,
+    /// or `None` where the tag gives the value itself
+    pub preset: Option<(Spanned<String>, Span)>,
+    /// Presets on the tag that give the attribute too, but lost out
+    pub overridden: Vec<Spanned<String>>,
+}
+
+/// Where every attribute in an expanded tree came from, by the [`Path`] to its tag
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Provenance {
+    pub tags: IndexMap<Path, IndexMap<String, Origin>>,
+}
+
+impl Provenance {
+    pub fn origin(&self, path: &[usize], key: &str) -> Option<&Origin> {
+        self.tags.get(path)?.get(key)
+    }
+}
+
+impl Presets {
+    /// Reads every root of the document as a `<preset>`, as in a file of presets
+    pub fn from_document(document: &Document) -> Result<Self, Vec<TagError>> {
+        let mut presets = Presets::default();
+        presets.read(document.tags())?;
+        Ok(presets)
+    }
+
+    /// Takes the `<preset>` roots out of the document, leaving everything else
+    pub fn take(document: &mut Document) -> Result<Self, Vec<TagError>> {
+        let (definitions, roots) = document.roots.drain(..).partition(|node| matches!(node, Node::Tag(tag) if tag.name == "preset"));
+        document.roots = roots;
+        let mut presets = Presets::default();
+        presets.read(definitions.iter().filter_map(|node| match node {
+            Node::Tag(tag) => Some(tag),
+            _ => None,
+        }))?;
+        Ok(presets)
+    }
+
+    /// Adds the definitions in `other`, whose attributes win where both give one
+    pub fn extend(&mut self, other: Presets) {
+        for (name, tags) in other.presets {
+            let entry = self.presets.entry(name).or_default();
+            for (tag, bundle) in tags {
+                entry.entry(tag).or_default().extend(bundle);
+            }
+        }
+    }
+
+    fn read<'t>(&mut self, definitions: impl Iterator<Item = &'t Tag>) -> Result<(), Vec<TagError>> {
+        let mut errors = Vec::new();
+        for definition in definitions {
+            if definition.name != "preset" {
+                errors.push(TagError::WrongTag { expected: "preset".into(), found: definition.name.value.clone(), span: definition.name.span });
+                continue;
+            }
+            let mut check = Check::new(definition, "preset");
+            let name = check.required::<String>("name");
+            let tags = check.count(0, None);
+            let mut bundles: IndexMap<String, Bundle> = IndexMap::new();
+            for tag in tags {
+                // A bundle is only ever attributes
+                let mut bundle = Check::new(tag, &tag.name);
+                for key in tag.attributes.keys() {
+                    bundle.value(key);
+                }
+                match bundle.finish() {
+                    Ok(()) => bundles.entry(tag.name.value.clone()).or_default().extend(tag.attributes.clone()),
+                    Err(found) => errors.extend(found),
+                }
+            }
+            match (check.finish(), name) {
+                (Ok(()), Some(name)) => self.extend(Presets { presets: IndexMap::from([(name, bundles)]) }),
+                (result, _) => errors.extend(result.err().unwrap_or_default()),
+            }
+        }
+        if errors.is_empty() {
+            return Ok(());
+        }
+        errors.sort_by_key(|error| error.span().start.offset);
+        Err(errors)
+    }
+
+    /// Gives `tag` and every tag within it the attributes of their presets
+    pub fn expand(&self, tag: &mut Tag) -> Provenance {
+        let mut provenance = Provenance::default();
+        self.expand_tag(tag, &mut Vec::new(), &mut provenance);
+        provenance
+    }
+
+    /// Expands every root of the document, where paths start with the index of the root
+    pub fn expand_document(&self, document: &mut Document) -> Provenance {
+        let mut provenance = Provenance::default();
+        for (i, node) in document.roots.iter_mut().enumerate() {
+            if let Node::Tag(tag) = node {
+                self.expand_tag(tag, &mut vec![i], &mut provenance);
+            }
+        }
+        provenance
+    }
+
+    fn expand_tag(&self, tag: &mut Tag, path: &mut Path, provenance: &mut Provenance) {
+        let mut origins: IndexMap<String, Origin> = tag.attributes.keys()
+            .map(|key| (key.value.clone(), Origin { preset: None, overridden: Vec::new() }))
+            .collect();
+        let mut resolved: IndexMap<String, Spanned<Value>> = IndexMap::new();
+        for name in &tag.traits {
+            let Some(bundle) = self.presets.get(name.as_str()).and_then(|tags| tags.get(tag.name.as_str())) else { continue };
+            for (key, value) in bundle {
+                let from = Some((name.clone(), value.span));
+                match origins.get_mut(key.as_str()) {
+                    Some(origin) if origin.preset.is_none() => origin.overridden.push(name.clone()),
+                    Some(origin) => {
+                        let (earlier, _) = std::mem::replace(&mut origin.preset, from).expect("checked to be from a preset");
+                        origin.overridden.push(earlier);
+                    }
+                    None => {
+                        origins.insert(key.value.clone(), Origin { preset: from, overridden: Vec::new() });
+                    }
+                }
+                if origins[key.as_str()].preset.is_some() {
+                    // Where the tag is concerned, the value is given by naming the preset
+                    resolved.insert(key.value.clone(), Spanned::new(value.value.clone(), name.span));
+                }
+            }
+        }
+        for (key, value) in resolved {
+            tag.attributes.insert(Spanned::new(key, value.span), value);
+        }
+        if !origins.is_empty() {
+            provenance.tags.insert(path.clone(), origins);
+        }
+
+        if let Inner::Children(children) = &mut tag.inner {
+            for (i, node) in children.iter_mut().enumerate() {
+                if let Node::Tag(child) = node {
+                    path.push(i);
+                    self.expand_tag(child, path, provenance);
+                    path.pop();
+                }
+            }
+        }
+    }
+}
+
+/// The presets each tag of the schema defines
+impl From<&Schema> for Presets {
+    fn from(schema: &Schema) -> Self {
+        let mut presets = Presets::default();
+        for (tag, definition) in &schema.tags {
+            for (name, bundle) in &definition.presets {
+                presets.presets.entry(name.clone()).or_default().insert(tag.clone(), bundle.clone());
+            }
+        }
+        presets
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{elements::{schema::Schema, TagError}, parser::{color::Color, document::Document, span::Spanned, tags::{Inner, Node, Tag}, values::Value}};
+
+    use super::Presets;
+
+    const PRESETS: &str = "\
+<preset name=\"primary\">
+    <button color=#3366ff size=12 />
+    <text color=#ffffff />
+</preset>
+<preset name=\"large\">
+    <button size=16 />
+</preset>
+";
+
+    #[test]
+    fn test_expand() {
+        let presets = Presets::from_document(&PRESETS.parse().unwrap()).unwrap();
+        let source = "<column><button primary large label=\"+\" color=red /><text primary>Hi</text></column>";
+        let mut tag: Tag = source.parse().unwrap();
+        let provenance = presets.expand(&mut tag);
+
+        let mut button = Tag::new("button");
+        button.set("primary").set("large")
+            .with("label", Value::String("+".into()))
+            .with("color", Value::Color(Color::rgb(255, 0, 0)))
+            .with("size", Value::Int(16));
+        let mut text = Tag::new("text");
+        text.set("primary").with("color", Value::Color(Color::rgb(255, 255, 255))).content("Hi");
+        let mut expected = Tag::new("column");
+        expected.children(vec![button, text]);
+        assert_eq!(expected, tag);
+
+        // The explicit color wins over the preset's, and the later preset's size over the earlier one's
+        let color = provenance.origin(&[0], "color").unwrap();
+        assert!(color.preset.is_none());
+        assert_eq!(vec![Spanned::from("primary")], color.overridden);
+        let size = provenance.origin(&[0], "size").unwrap();
+        let (preset, definition) = size.preset.as_ref().unwrap();
+        assert_eq!("large", preset.value);
+        assert_eq!("16", &PRESETS[definition.bytes()]);
+        assert_eq!(vec![Spanned::from("primary")], size.overridden);
+        assert_eq!("primary", provenance.origin(&[1], "color").unwrap().preset.as_ref().unwrap().0.value);
+        assert!(provenance.origin(&[], "color").is_none());
+
+        // The expanded value points at where the tag names its preset
+        let Inner::Children(children) = &tag.inner else { panic!("Expected children") };
+        let Node::Tag(button) = &children[0] else { panic!("Expected a tag") };
+        assert_eq!("large", &source[button.attributes["size"].span.bytes()]);
+    }
+
+    #[test]
+    fn test_definitions() {
+        let mut document: Document = "<preset name=\"wide\"><row gap=8 /></preset>\n<row wide />".parse().unwrap();
+        let presets = Presets::take(&mut document).unwrap();
+        assert_eq!(1, document.roots.len());
+        let provenance = presets.expand_document(&mut document);
+        assert_eq!(Value::Int(8), *document.tags().next().unwrap().attributes["gap"]);
+        assert!(provenance.origin(&[0], "gap").is_some());
+
+        let text = "<preset><row big>Text</row></preset><row />";
+        let errors = Presets::from_document(&text.parse().unwrap()).unwrap_err();
+        assert!(matches!(&errors[..], [
+            TagError::MissingAttribute { .. },
+            TagError::UnknownPreset { .. },
+            TagError::UnexpectedContent { .. },
+            TagError::WrongTag { .. },
+        ]), "{errors:?}");
+        assert_eq!("row", &text[errors[3].span().bytes()]);
+
+        let schema: Schema = "<tag name=\"button\"><attribute name=\"color\" type=\"color\" /><preset name=\"primary\" color=blue /></tag>".parse().unwrap();
+        let mut button: Tag = "<button primary />".parse().unwrap();
+        Presets::from(&schema).expand(&mut button);
+        assert_eq!(Value::Color(Color::rgb(0, 0, 255)), *button.attributes["color"]);
+    }
+}
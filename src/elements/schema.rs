@@ -0,0 +1,478 @@
+//! Tags described in a schema file rather than in Rust, for custom tags with no element of their own.
+//! A schema is markup too, with one `<tag>` for each tag it allows:
+//! ```xml
+//! <!doctype bftml-schema version=1>
+//! <tag name="button" children="content">
+//!     <attribute name="label" type="string" />
+//!     <attribute optional name="color" type="color" />
+//!     <preset name="primary" color=#3366ff />
+//! </tag>
+//! ```
+//! Attributes are required unless marked `optional`, though one that a preset on the tag gives a default for
+//! can be left out too. A tag takes `none` (the default), `one` or `many` children, or `content`.
+
+use std::{fmt, fs, io, path::{Path, PathBuf}, str::FromStr};
+
+use indexmap::IndexMap;
+
+use crate::parser::{document::Document, error::SyntaxError, span::{Span, Spanned}, tags::{Inner, Node, Tag}, values::{FromValue, Value}};
+
+use super::{presets::Bundle, Check, Child, Element, TagError};
+
+/// The tags a document may use, each with what it permits
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Schema {
+    pub tags: IndexMap<String, TagSchema>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TagSchema {
+    pub name: String,
+    pub attributes: IndexMap<String, AttributeSchema>,
+    /// Each preset, with the defaults it gives for attributes
+    pub presets: IndexMap<String, Bundle>,
+    pub children: Children,
+}
+
+#[derive(Clone, Debug, Element, PartialEq)]
+#[element(name = "attribute")]
+pub struct AttributeSchema {
+    #[element(preset)]
+    pub optional: bool,
+    pub name: String,
+    #[element(name = "type")]
+    pub ty: ValueType,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Children {
+    #[default]
+    None,
+    One,
+    Many,
+    Content,
+}
+
+/// The kinds of [`Value`] an attribute can be declared as
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueType {
+    Any,
+    String,
+    Int,
+    /// Integers will do too
+    Float,
+    Bool,
+    Range,
+    /// Integer ranges will do too
+    FloatRange,
+    Color,
+    Length,
+    List,
+    Map,
+    Expr,
+}
+
+impl ValueType {
+    pub fn accepts(self, value: &Value) -> bool {
+        matches!(
+            (self, value),
+            (ValueType::Any, _)
+                | (ValueType::String, Value::String(_))
+                | (ValueType::Int, Value::Int(_))
+                | (ValueType::Float, Value::Float(_) | Value::Int(_))
+                | (ValueType::Bool, Value::Bool(_))
+                | (ValueType::Range, Value::Range(_))
+                | (ValueType::FloatRange, Value::FloatRange(_) | Value::Range(_))
+                | (ValueType::Color, Value::Color(_))
+                | (ValueType::Length, Value::Length(_))
+                | (ValueType::List, Value::List(_))
+                | (ValueType::Map, Value::Map(_))
+                | (ValueType::Expr, Value::Expr(_))
+        )
+    }
+
+    /// In the same words as [`Value::kind`]
+    pub fn expected(self) -> &'static str {
+        match self {
+            ValueType::Any => "a value",
+            ValueType::String => "a string",
+            ValueType::Int => "an integer",
+            ValueType::Float => "a number",
+            ValueType::Bool => "a boolean",
+            ValueType::Range => "an integer range",
+            ValueType::FloatRange => "a range",
+            ValueType::Color => "a color",
+            ValueType::Length => "a length",
+            ValueType::List => "a list",
+            ValueType::Map => "a map",
+            ValueType::Expr => "an expression",
+        }
+    }
+}
+
+impl FromValue for ValueType {
+    const EXPECTED: &'static str =
+        "one of `any`, `string`, `int`, `float`, `bool`, `range`, `float-range`, `color`, `length`, `list`, `map` or `expr`";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        Some(match value.as_str()? {
+            "any" => ValueType::Any,
+            "string" => ValueType::String,
+            "int" => ValueType::Int,
+            "float" => ValueType::Float,
+            "bool" => ValueType::Bool,
+            "range" => ValueType::Range,
+            "float-range" => ValueType::FloatRange,
+            "color" => ValueType::Color,
+            "length" => ValueType::Length,
+            "list" => ValueType::List,
+            "map" => ValueType::Map,
+            "expr" => ValueType::Expr,
+            _ => return None,
+        })
+    }
+}
+
+impl FromValue for Children {
+    const EXPECTED: &'static str = "one of `none`, `one`, `many` or `content`";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        Some(match value.as_str()? {
+            "none" => Children::None,
+            "one" => Children::One,
+            "many" => Children::Many,
+            "content" => Children::Content,
+            _ => return None,
+        })
+    }
+}
+
+impl Schema {
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        let path = path.as_ref().to_path_buf();
+        let text = fs::read_to_string(&path).map_err(|error| LoadError::Io { path: path.clone(), error })?;
+        text.parse().map_err(|error| LoadError::Invalid { path, text, error })
+    }
+
+    pub fn from_document(document: &Document) -> Result<Self, Vec<TagError>> {
+        let mut tags = IndexMap::new();
+        let mut defined = IndexMap::new();
+        let mut errors = Vec::new();
+        for tag in document.tags() {
+            // Even a tag that is otherwise invalid is defined, so a second one is not mistaken for a fix
+            if let Ok(name) = tag.require::<String>("name") {
+                errors.extend(define(&mut defined, "tag", &name, named(tag)));
+            }
+            match TagSchema::from_tag(tag) {
+                Ok(schema) => {
+                    tags.insert(schema.name.clone(), schema);
+                }
+                Err(found) => errors.extend(found),
+            }
+        }
+        if errors.is_empty() { Ok(Schema { tags }) } else { Err(errors) }
+    }
+
+    /// Checks the tag and everything in it, giving back every error in the order they appear
+    pub fn validate(&self, tag: &Tag) -> Vec<TagError> {
+        let mut errors = Vec::new();
+        self.check(tag, &mut errors);
+        errors.sort_by_key(|error| error.span().start.offset);
+        errors
+    }
+
+    /// Roots follow one another, so their errors stay in order
+    pub fn validate_document(&self, document: &Document) -> Vec<TagError> {
+        document.tags().flat_map(|tag| self.validate(tag)).collect()
+    }
+
+    fn check(&self, tag: &Tag, errors: &mut Vec<TagError>) {
+        let Some(schema) = self.tags.get(tag.name.as_str()) else {
+            errors.push(TagError::UnknownTag { name: tag.name.value.clone(), span: tag.name.span });
+            // Whatever is inside might still be known
+            if let Inner::Children(children) = &tag.inner {
+                for node in children {
+                    if let Node::Tag(child) = node {
+                        self.check(child, errors);
+                    }
+                }
+            }
+            return;
+        };
+
+        let mut check = Check::new(tag, &schema.name);
+        for name in schema.presets.keys() {
+            check.preset(name);
+        }
+        for (key, attribute) in &schema.attributes {
+            match check.value(key) {
+                Some(value) if !attribute.ty.accepts(value) => check.wrong_type(key, attribute.ty.expected(), value),
+                None if !attribute.optional && schema.default(tag, key).is_none() => check.missing(key),
+                _ => {}
+            }
+        }
+        let children = match schema.children {
+            Children::None => Vec::new(),
+            Children::One => check.count(1, Some(1)),
+            Children::Many => check.count(0, None),
+            Children::Content => {
+                check.content();
+                Vec::new()
+            }
+        };
+        if let Err(found) = check.finish() {
+            errors.extend(found);
+        }
+        for child in children {
+            self.check(child, errors);
+        }
+    }
+}
+
+impl TagSchema {
+    /// The default a preset on `tag` gives for an attribute, where later presets win over earlier ones
+    pub fn default(&self, tag: &Tag, key: &str) -> Option<&Spanned<Value>> {
+        tag.traits.iter().rev().find_map(|name| self.presets.get(name.as_str())?.get(key))
+    }
+}
+
+impl Element for TagSchema {
+    const NAME: &'static str = "tag";
+
+    fn from_tag(tag: &Tag) -> Result<Self, Vec<TagError>> {
+        let mut check = Check::new(tag, Self::NAME);
+        let name = check.required::<String>("name");
+        let children = check.attribute::<Children>("children").unwrap_or_default();
+        let parts = check.children::<Part>(0, None);
+        let mut errors = check.finish().err().unwrap_or_default();
+
+        let name = name.unwrap_or_default();
+        let mut attributes = IndexMap::new();
+        let mut presets = IndexMap::new();
+        let mut defined = IndexMap::new();
+        for part in parts {
+            match part {
+                Part::Attribute(attribute, span) => {
+                    errors.extend(define(&mut defined, "attribute", &attribute.name, span));
+                    attributes.insert(attribute.name.clone(), attribute);
+                }
+                Part::Preset(preset, span) => {
+                    errors.extend(define(&mut defined, "preset", &preset.name, span));
+                    presets.insert(preset.name, preset.defaults);
+                }
+            }
+        }
+        // Defaults are checked up front, so a tag using the preset can rely on them
+        for (key, value) in presets.values().flatten() {
+            match attributes.get(key.as_str()) {
+                None => errors.push(TagError::UnknownAttribute { tag: name.clone(), key: key.clone() }),
+                Some(attribute) if !attribute.ty.accepts(value) => {
+                    errors.push(TagError::WrongType { key: key.value.clone(), expected: attribute.ty.expected(), found: value.kind(), span: value.span });
+                }
+                Some(_) => {}
+            }
+        }
+        if !errors.is_empty() {
+            errors.sort_by_key(|error| error.span().start.offset);
+            return Err(errors);
+        }
+        Ok(TagSchema { name, attributes, presets, children })
+    }
+}
+
+/// A `<preset>`, where every attribute but the name is a default
+struct PresetSchema {
+    name: String,
+    defaults: Bundle,
+}
+
+impl Element for PresetSchema {
+    const NAME: &'static str = "preset";
+
+    fn from_tag(tag: &Tag) -> Result<Self, Vec<TagError>> {
+        let mut check = Check::new(tag, Self::NAME);
+        let name = check.required::<String>("name");
+        let mut defaults = IndexMap::new();
+        for (key, value) in &tag.attributes {
+            if key.value != "name" {
+                check.value(key);
+                defaults.insert(key.clone(), value.clone());
+            }
+        }
+        check.finish()?;
+        Ok(PresetSchema { name: name.expect("checked to be present"), defaults })
+    }
+}
+
+/// What a `<tag>` is made of, each along with where its name is given
+enum Part {
+    Attribute(AttributeSchema, Span),
+    Preset(PresetSchema, Span),
+}
+
+impl Child for Part {
+    fn from_child(tag: &Tag) -> Result<Self, Vec<TagError>> {
+        match tag.name.as_str() {
+            AttributeSchema::NAME => AttributeSchema::from_tag(tag).map(|attribute| Part::Attribute(attribute, named(tag))),
+            PresetSchema::NAME => PresetSchema::from_tag(tag).map(|preset| Part::Preset(preset, named(tag))),
+            _ => Err(vec![TagError::UnknownTag { name: tag.name.value.clone(), span: tag.name.span }]),
+        }
+    }
+}
+
+/// Where a definition gives its name, which it has once it is known to be valid
+fn named(tag: &Tag) -> Span {
+    tag.attributes.get("name").map_or(tag.span, |name| name.span)
+}
+
+/// Notes where the `kind` called `name` is defined, as long as it was not defined already
+fn define(defined: &mut IndexMap<(&'static str, String), Span>, kind: &'static str, name: &str, span: Span) -> Option<TagError> {
+    let first = defined.insert((kind, name.into()), span)?;
+    Some(TagError::Duplicate { kind, name: name.into(), first, second: span })
+}
+
+impl FromStr for Schema {
+    type Err = SchemaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let document: Document = s.parse().map_err(|error| SchemaError::Syntax(Box::new(error)))?;
+        Schema::from_document(&document).map_err(SchemaError::Tags)
+    }
+}
+
+/// Why text is not a valid schema
+#[derive(Debug)]
+pub enum SchemaError {
+    Syntax(Box<SyntaxError>),
+    Tags(Vec<TagError>),
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaError::Syntax(error) => write!(f, "{error}"),
+            SchemaError::Tags(errors) => {
+                for (i, error) in errors.iter().enumerate() {
+                    write!(f, "{}{error}", if i == 0 { "" } else { "\n" })?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+/// Why a schema could not be loaded from a file
+#[derive(Debug)]
+pub enum LoadError {
+    Io { path: PathBuf, error: io::Error },
+    /// Keeps the text so the errors can be rendered as [`Report`](crate::parser::error::Report)s
+    Invalid { path: PathBuf, text: String, error: SchemaError },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io { path, error } => write!(f, "could not read {}: {error}", path.display()),
+            LoadError::Invalid { path, error, .. } => write!(f, "{}: {error}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io { error, .. } => Some(error),
+            LoadError::Invalid { error, .. } => Some(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{elements::TagError, parser::{color::Color, document::Document, error::Diagnostic, values::Value}};
+
+    use super::{Children, Schema, SchemaError, ValueType};
+
+    const SCHEMA: &str = "\
+<!doctype bftml-schema version=1>
+<tag name=\"column\" children=\"many\">
+    <attribute optional name=\"gap\" type=\"length\" />
+</tag>
+<tag name=\"button\" children=\"content\">
+    <attribute name=\"label\" type=\"string\" />
+    <attribute name=\"color\" type=\"color\" />
+    <attribute optional name=\"width\" type=\"float\" />
+    <preset name=\"primary\" color=#3366ff />
+</tag>
+<tag name=\"spacer\" />
+";
+
+    fn validate(text: &str) -> Vec<TagError> {
+        let schema: Schema = SCHEMA.parse().unwrap();
+        schema.validate_document(&text.parse::<Document>().unwrap())
+    }
+
+    #[test]
+    fn test_schema() {
+        let schema: Schema = SCHEMA.parse().unwrap();
+        let button = &schema.tags["button"];
+        assert_eq!(Children::Content, button.children);
+        assert_eq!(ValueType::Color, button.attributes["color"].ty);
+        assert!(button.attributes["width"].optional);
+        assert_eq!(Value::Color(Color::rgb(0x33, 0x66, 0xff)), *button.presets["primary"]["color"]);
+        assert_eq!(Children::None, schema.tags["spacer"].children);
+
+        let Err(SchemaError::Tags(errors)) = "<tag name=\"a\" children=\"all\"><attribute name=\"b\" type=\"text\" /><attribute name=\"e\" type=\"int\" /><preset name=\"c\" d=2 e=\"x\" /><property /></tag>".parse::<Schema>() else {
+            panic!("Expected tag errors");
+        };
+        assert!(matches!(&errors[..], [
+            TagError::WrongType { key, .. },
+            TagError::WrongType { .. },
+            TagError::UnknownAttribute { .. },
+            TagError::WrongType { expected: "an integer", found: "a string", .. },
+            TagError::UnknownTag { .. },
+        ] if key == "children"), "{errors:?}");
+        assert_eq!("attribute `type` should be one of `any`, `string`, `int`, `float`, `bool`, `range`, `float-range`, `color`, `length`, `list`, `map` or `expr`", errors[1].message());
+        assert!(matches!("<tag>".parse::<Schema>(), Err(SchemaError::Syntax(_))));
+
+        // Nothing is defined twice, where the definitions would otherwise quietly replace one another
+        let text = "<tag name=\"a\"><attribute name=\"b\" type=\"int\" /><attribute name=\"b\" type=\"float\" /><preset name=\"c\" /><preset name=\"c\" b=1 /></tag>\n<tag name=\"a\" />";
+        let Err(SchemaError::Tags(errors)) = text.parse::<Schema>() else { panic!("Expected tag errors") };
+        let [
+            TagError::Duplicate { kind: "attribute", name: attribute, first, second },
+            TagError::Duplicate { kind: "preset", name: preset, .. },
+            TagError::Duplicate { kind: "tag", name: tag, .. },
+        ] = &errors[..] else {
+            panic!("{errors:?}");
+        };
+        assert_eq!(("b", "c", "a"), (attribute.as_str(), preset.as_str(), tag.as_str()));
+        assert_eq!(("\"b\"", "\"b\""), (&text[first.bytes()], &text[second.bytes()]));
+        assert!(first.start.offset < second.start.offset);
+        assert_eq!("attribute `b` defined more than once", errors[0].message());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate("<column gap=4lp><button primary label=\"+\">Add</button><spacer /></column>").is_empty());
+        // Integers will do for floats
+        assert!(validate("<button label=\"\" color=red width=40 />").is_empty());
+
+        let errors = validate("<column gap=4><button label=\"+\"><spacer /></button><slider /></column>");
+        assert!(matches!(&errors[..], [
+            TagError::WrongType { expected: "a length", found: "an integer", .. },
+            TagError::MissingAttribute { .. },
+            TagError::ChildCount { max: Some(0), .. },
+            TagError::UnknownTag { .. },
+        ]), "{errors:?}");
+        assert_eq!("`<slider>` is not a known tag", errors[3].message());
+
+        // Unknown tags are still looked into
+        let errors = validate("<row><spacer big /></row>");
+        assert!(matches!(&errors[..], [TagError::UnknownTag { .. }, TagError::UnknownPreset { .. }]), "{errors:?}");
+        let schema: Schema = SCHEMA.parse().unwrap();
+        let errors = schema.validate(&"<spacer>Text</spacer>".parse().unwrap());
+        assert!(matches!(&errors[..], [TagError::UnexpectedContent { .. }]), "{errors:?}");
+    }
+}
@@ -1,21 +1,45 @@
 use indexmap::IndexMap;
-use winnow::{ascii::{alphanumeric1, space0}, combinator::repeat, Result, Parser};
+use winnow::{ascii::multispace0, combinator::repeat, error::ErrMode, token::take_till, Parser};
 
-use super::values::{value, Value};
+use super::{error::{Result, SyntaxError}, ident::identifier, span::{spanned, Input, Spanned}, values::{value, Value}};
 
-pub fn single<'s>(s: &mut &'s str) -> Result<(&'s str, Value)> {
-    let _ = space0.parse_next(s)?;
-    let key = alphanumeric1.parse_next(s)?;
-    let _ = "=".parse_next(s)?;
-    let value = value.parse_next(s)?;
-    Ok((key, value))
+pub fn single<'s>(s: &mut Input<'s>) -> Result<(Spanned<&'s str>, Spanned<Value>)> {
+    with(value).parse_next(s)
 }
 
-pub fn many<'s>(s: &mut &'s str) -> Result<IndexMap<String, Value>> {
-    repeat(0.., single).fold(IndexMap::new, |mut acc: IndexMap<_, _>, item| {
-        acc.insert(item.0.into(), item.1.into());
-        acc
-    }).parse_next(s)
+/// An attribute whose value is parsed by `value`, for trees that keep values their own way
+pub(crate) fn with<'s, O>(mut value: impl Parser<Input<'s>, O, ErrMode<SyntaxError>>) -> impl Parser<Input<'s>, (Spanned<&'s str>, Spanned<O>), ErrMode<SyntaxError>> {
+    move |s: &mut Input<'s>| {
+        let _ = multispace0.parse_next(s)?;
+        let key = spanned(identifier).parse_next(s)?;
+        let _ = "=".parse_next(s)?;
+        let value = spanned(value.by_ref()).parse_next(s).map_err(|e| match e {
+            ErrMode::Backtrack(_) => bad_value(s, key.value),
+            cut => cut,
+        })?;
+        Ok((key, value))
+    }
+}
+
+/// Blames everything up to the end of the attribute for an unparseable value
+fn bad_value(s: &mut Input, key: &str) -> ErrMode<SyntaxError> {
+    let end = |c: char| c.is_whitespace() || c == '>' || c == '/';
+    match spanned(take_till(0.., end)).parse_next(s) {
+        Ok(bad) => SyntaxError::BadValue { key: key.into(), span: bad.span }.cut(),
+        Err(e) => e,
+    }
+}
+
+pub fn many(s: &mut Input) -> Result<IndexMap<Spanned<String>, Spanned<Value>>> {
+    let items: Vec<_> = repeat(0.., single).parse_next(s)?;
+    let mut attributes: IndexMap<Spanned<String>, _> = IndexMap::with_capacity(items.len());
+    for (key, value) in items {
+        if let Some((first, _)) = attributes.get_key_value(key.value) {
+            return Err(SyntaxError::DuplicateAttribute { name: key.value.into(), first: first.span, second: key.span }.cut());
+        }
+        attributes.insert(key.map(String::from), value);
+    }
+    Ok(attributes)
 }
 
 #[cfg(test)]
@@ -23,33 +47,38 @@ mod test {
     use indexmap::IndexMap;
     use winnow::Parser;
 
+    use crate::parser::span::{Source, Spanned};
+
     use super::{many, Value};
 
     use super::single;
 
     #[test]
     fn test_parse_attr() {
-        let mut input = "foo=\"bar\"";
+        let source = Source::new("foo=\"bar\"");
+        let mut input = source.input();
         let parsed = single.parse_next(&mut input);
-        assert_eq!(input, "");
+        assert_eq!(**input, "");
         assert!(parsed.is_ok());
         let pair = parsed.unwrap();
-        assert_eq!(pair.0, "foo");
-        assert_eq!(pair.1, Value::String("bar".into()));
+        assert_eq!(*pair.0, "foo");
+        assert_eq!(*pair.1, Value::String("bar".into()));
+        assert_eq!(4..9, pair.1.span.bytes());
     }
 
     #[test]
     fn test_parse_attrs() {
-        let mut input = "foo=\"bar\" bo=\"burnham\"";
+        let source = Source::new("foo=\"bar\" bo=\"burnham\"");
+        let mut input = source.input();
         let parsed = many.parse_next(&mut input);
-        assert_eq!(input, "");
+        assert_eq!(**input, "");
         assert!(parsed.is_ok());
 
-        let mut expected: IndexMap<String, Value> = IndexMap::new();
-        expected.insert("foo".into(), Value::String("bar".into()));
-        expected.insert("bo".into(), Value::String("burnham".into()));
+        let mut expected: IndexMap<Spanned<String>, Spanned<Value>> = IndexMap::new();
+        expected.insert("foo".into(), Value::String("bar".into()).into());
+        expected.insert("bo".into(), Value::String("burnham".into()).into());
 
         let actual = parsed.unwrap();
         assert_eq!(expected, actual);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,165 @@
+//! A tree borrowing from the text it was parsed from, for loading large documents without
+//! allocating for every name, key, string and run of content. It parses the same grammar as [`tags`](super::tags),
+//! and converts to the owned tree with [`Tag::into_owned`]. It does not recover from errors.
+
+use std::borrow::Cow;
+
+use winnow::{ascii::multispace0, combinator::{alt, preceded, repeat}, Parser};
+
+use super::{attributes, error::{self, Result, SyntaxError}, span::{Input, Source, Span, Spanned}, tags::{self, comment, open, Body, Comments}, traits, values::{self, string}};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Inner<'src> {
+    None,
+    /// Only owned when entities or comments had to be taken out of it
+    Content(Spanned<Cow<'src, str>>),
+    Children(Vec<Node<'src>>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Node<'src> {
+    Tag(Tag<'src>),
+    /// Only present when parsing with [`Source::keeping_comments`]
+    Comment(Spanned<&'src str>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value<'src> {
+    /// Only owned when escapes had to be replaced
+    String(Cow<'src, str>),
+    /// Any other kind of value, owned as in the owned tree
+    Other(values::Value),
+}
+
+impl Value<'_> {
+    pub fn into_owned(self) -> values::Value {
+        match self {
+            Value::String(s) => values::Value::String(s.into_owned()),
+            Value::Other(value) => value,
+        }
+    }
+}
+
+/// Like [`tags::Tag`], but presets and attributes are kept in plain lists in the order they were written
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tag<'src> {
+    pub name: Spanned<&'src str>,
+    pub traits: Vec<Spanned<&'src str>>,
+    pub attributes: Vec<(Spanned<&'src str>, Spanned<Value<'src>>)>,
+    pub inner: Inner<'src>,
+    pub comments: Comments<&'src str>,
+    pub span: Span,
+}
+
+impl<'src> Tag<'src> {
+    /// Parses a whole source as one tag. The source is borrowed rather than built here,
+    /// as the tree cannot outlive it.
+    pub fn parse(source: &'src Source<'src>) -> Result<Self, SyntaxError> {
+        let mut input = source.input();
+        let tag = single(&mut input).and_then(|tag| super::document::end(&mut input).map(|()| tag));
+        tag.map_err(error::finish)
+    }
+
+    pub fn has_trait(&self, name: &str) -> bool {
+        self.traits.iter().any(|t| t.value == name)
+    }
+
+    pub fn attribute(&self, key: &str) -> Option<&Spanned<Value<'src>>> {
+        self.attributes.iter().find(|(k, _)| k.value == key).map(|(_, value)| value)
+    }
+
+    pub fn into_owned(self) -> tags::Tag {
+        let inner = match self.inner {
+            Inner::None => tags::Inner::None,
+            Inner::Content(text) => tags::Inner::Content(text.map(Cow::into_owned)),
+            Inner::Children(children) => tags::Inner::Children(children.into_iter().map(Node::into_owned).collect()),
+        };
+        tags::Tag {
+            name: self.name.map(String::from),
+            traits: self.traits.into_iter().map(|t| t.map(String::from)).collect(),
+            attributes: self.attributes.into_iter().map(|(key, value)| (key.map(String::from), value.map(Value::into_owned))).collect(),
+            inner,
+            comments: self.comments.into_iter().map(|(offset, comment)| (offset, comment.map(String::from))).collect(),
+            span: self.span,
+        }
+    }
+}
+
+impl Node<'_> {
+    pub fn into_owned(self) -> tags::Node {
+        match self {
+            Node::Tag(tag) => tags::Node::Tag(tag.into_owned()),
+            Node::Comment(text) => tags::Node::Comment(text.map(String::from)),
+        }
+    }
+}
+
+pub fn single<'s>(s: &mut Input<'s>) -> Result<Tag<'s>> {
+    let (start, name) = open.parse_next(s)?;
+    let traits: Vec<Spanned<&str>> = repeat(0.., traits::single).parse_next(s)?;
+    if let Some((first, second)) = duplicate(&traits) {
+        return Err(SyntaxError::DuplicatePreset { name: second.value.into(), first: first.span, second: second.span }.cut());
+    }
+    let value = alt((string.map(Value::String), values::value.map(Value::Other)));
+    let attributes: Vec<(Spanned<&str>, Spanned<Value>)> = repeat(0.., attributes::with(value)).parse_next(s)?;
+    let keys: Vec<Spanned<&str>> = attributes.iter().map(|(key, _)| *key).collect();
+    if let Some((first, second)) = duplicate(&keys) {
+        return Err(SyntaxError::DuplicateAttribute { name: second.value.into(), first: first.span, second: second.span }.cut());
+    }
+
+    let preserve = traits.iter().any(|name| name.value == tags::PRESERVE);
+    let (body, span) = tags::body(s, start, name, preserve, many)?;
+    let (inner, comments) = match body {
+        Body::Empty => (Inner::None, Vec::new()),
+        Body::Children(children) => (Inner::Children(children), Vec::new()),
+        Body::Content(text, comments) => (Inner::Content(text), comments),
+    };
+    Ok(Tag { name, traits, attributes, inner, comments, span })
+}
+
+/// The first name given twice, along with where it was first given. Tags have few enough of these
+/// that comparing each with the ones before it is cheaper than hashing them.
+fn duplicate<'a, 's>(names: &'a [Spanned<&'s str>]) -> Option<(&'a Spanned<&'s str>, &'a Spanned<&'s str>)> {
+    names.iter().enumerate().find_map(|(i, second)| Some((names[..i].iter().find(|first| first.value == second.value)?, second)))
+}
+
+fn many<'s>(s: &mut Input<'s>) -> Result<Vec<Node<'s>>> {
+    let keep = s.state.keep_comments;
+    repeat(1.., alt((single.map(Node::Tag), preceded(multispace0, comment).map(Node::Comment))))
+        .verify(|nodes: &Vec<Node>| nodes.iter().any(|node| matches!(node, Node::Tag(_))))
+        .map(|mut nodes: Vec<Node>| {
+            nodes.retain(|node| keep || matches!(node, Node::Tag(_)));
+            nodes
+        })
+        .parse_next(s)
+}
+
+#[cfg(test)]
+mod test {
+    use std::borrow::Cow;
+
+    use crate::parser::{span::Source, tags, values};
+
+    use super::{Inner, Tag, Value};
+
+    #[test]
+    fn test_borrowed() {
+        let text = "<foo b a=1 c=\"plain\" d=\"\\\"quoted\\\"\"><bar>Ba &amp; nana</bar><baz>plain</baz></foo>";
+        let source = Source::new(text);
+        let tag = Tag::parse(&source).unwrap();
+        assert_eq!("foo", tag.name.value);
+        assert_eq!("foo", &text[tag.name.span.bytes()]);
+        assert!(tag.has_trait("b"));
+        assert!(!tag.has_trait("a"));
+        assert_eq!(Some(&Value::Other(values::Value::Int(1))), tag.attribute("a").map(|v| &v.value));
+        assert!(matches!(tag.attribute("c").unwrap().value, Value::String(Cow::Borrowed("plain"))));
+        assert!(matches!(&tag.attribute("d").unwrap().value, Value::String(Cow::Owned(s)) if s == "\"quoted\""));
+        let Inner::Children(children) = &tag.inner else { panic!("Expected children") };
+        let super::Node::Tag(baz) = &children[1] else { panic!("Expected a tag") };
+        assert!(matches!(&baz.inner, Inner::Content(text) if matches!(text.value, Cow::Borrowed("plain"))));
+        assert_eq!(text.parse::<tags::Tag>().unwrap(), tag.into_owned());
+        assert!(Tag::parse(&Source::new("<foo></bar>")).is_err());
+        let duplicate = Tag::parse(&Source::new("<foo a=1 a=2 />")).unwrap_err();
+        assert_eq!("<foo a=1 a=2 />".parse::<tags::Tag>().unwrap_err(), duplicate);
+    }
+}
@@ -0,0 +1,210 @@
+use std::fmt;
+
+use winnow::{ascii::space0, combinator::{alt, cut_err, delimited, not, opt, separated, terminated}, stream::AsChar, token::take_while, Parser};
+
+use super::{error::{Result, SyntaxError}, ident, span::{spanned, Input, Spanned}, values::number};
+
+/// An sRGB color as written in markup, with 8 bits per channel
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Color { r, g, b, a: 255 }
+    }
+
+    /// The color in linear space, as the shaders blend in it before writing to an sRGB surface
+    pub fn to_linear(self) -> [f32; 4] {
+        let linear = |c: u8| {
+            let c = c as f32 / 255.0;
+            if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+        };
+        [linear(self.r), linear(self.g), linear(self.b), self.a as f32 / 255.0]
+    }
+
+    pub fn named(name: &str) -> Option<Self> {
+        NAMED.iter().find(|(n, _)| *n == name).map(|(_, color)| *color)
+    }
+}
+
+impl From<Color> for [f32; 4] {
+    fn from(color: Color) -> Self {
+        color.to_linear()
+    }
+}
+
+/// Writes `#rrggbb`, or `#rrggbbaa` when not opaque
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)?;
+        if self.a != 255 {
+            write!(f, "{:02x}", self.a)?;
+        }
+        Ok(())
+    }
+}
+
+/// The basic CSS colors, plus `orange` and `transparent`
+const NAMED: [(&str, Color); 18] = [
+    ("black", Color::rgb(0, 0, 0)),
+    ("silver", Color::rgb(192, 192, 192)),
+    ("gray", Color::rgb(128, 128, 128)),
+    ("white", Color::rgb(255, 255, 255)),
+    ("maroon", Color::rgb(128, 0, 0)),
+    ("red", Color::rgb(255, 0, 0)),
+    ("purple", Color::rgb(128, 0, 128)),
+    ("fuchsia", Color::rgb(255, 0, 255)),
+    ("green", Color::rgb(0, 128, 0)),
+    ("lime", Color::rgb(0, 255, 0)),
+    ("olive", Color::rgb(128, 128, 0)),
+    ("yellow", Color::rgb(255, 255, 0)),
+    ("navy", Color::rgb(0, 0, 128)),
+    ("blue", Color::rgb(0, 0, 255)),
+    ("teal", Color::rgb(0, 128, 128)),
+    ("aqua", Color::rgb(0, 255, 255)),
+    ("orange", Color::rgb(255, 165, 0)),
+    ("transparent", Color { r: 0, g: 0, b: 0, a: 0 }),
+];
+
+/// `#rgb`, `#rgba`, `#rrggbb`, `#rrggbbaa`, `rgb()`, `rgba()`, `hsl()`, `hsla()` or a named color
+pub fn color(s: &mut Input) -> Result<Color> {
+    alt((hex, function, named)).parse_next(s)
+}
+
+/// Only a whole name counts, so neither `green-ish` nor a call to `green()` is taken for `green`
+fn named(s: &mut Input) -> Result<Color> {
+    terminated(ident::name, not('(')).verify_map(Color::named).parse_next(s)
+}
+
+fn hex(s: &mut Input) -> Result<Color> {
+    let _ = '#'.parse_next(s)?;
+    let digits = cut_err(take_while(1.., AsChar::is_hex_digit).verify(|d: &str| matches!(d.len(), 3 | 4 | 6 | 8)))
+        .context("3, 4, 6 or 8 hex digits")
+        .parse_next(s)?;
+    let channels: Vec<u8> = if digits.len() <= 4 {
+        digits.chars().map(|c| c.to_digit(16).unwrap() as u8 * 17).collect()
+    } else {
+        (0..digits.len()).step_by(2).map(|i| u8::from_str_radix(&digits[i..i + 2], 16).unwrap()).collect()
+    };
+    Ok(Color { r: channels[0], g: channels[1], b: channels[2], a: channels.get(3).copied().unwrap_or(255) })
+}
+
+/// A number, and whether it was written as a percentage
+fn argument(s: &mut Input) -> Result<Spanned<(f64, bool)>> {
+    spanned((number, opt('%').map(|p| p.is_some()))).parse_next(s)
+}
+
+fn function(s: &mut Input) -> Result<Color> {
+    let name = alt(("rgba", "rgb", "hsla", "hsl")).parse_next(s)?;
+    let _ = '('.parse_next(s)?;
+    let count = if name.len() == 4 { 4 } else { 3 };
+    let args: Vec<_> = cut_err(delimited(space0, separated(count, argument, (space0, ',', space0)), (space0, ')')))
+        .context(if count == 4 { "four comma separated numbers" } else { "three comma separated numbers" })
+        .parse_next(s)?;
+
+    let a = match args.get(3) {
+        Some(alpha) => channel(alpha, 1.0)?,
+        None => 255,
+    };
+    if name.starts_with("rgb") {
+        Ok(Color { r: channel(&args[0], 255.0)?, g: channel(&args[1], 255.0)?, b: channel(&args[2], 255.0)?, a })
+    } else {
+        let hue = args[0].value.0.rem_euclid(360.0);
+        let (saturation, lightness) = (percent(&args[1])?, percent(&args[2])?);
+        let [r, g, b] = hsl_to_rgb(hue, saturation, lightness);
+        Ok(Color { r, g, b, a })
+    }
+}
+
+/// Scales a channel given out of `max`, or as a percentage, to 8 bits
+fn channel(arg: &Spanned<(f64, bool)>, max: f64) -> Result<u8> {
+    let (value, percent) = arg.value;
+    let fraction = if percent { value / 100.0 } else { value / max };
+    if !(0.0..=1.0).contains(&fraction) {
+        let expected = if max == 1.0 { "an alpha from 0 to 1, or 0% to 100%" } else { "a channel from 0 to 255, or 0% to 100%" };
+        return Err(SyntaxError::Expected { expected, span: arg.span }.cut());
+    }
+    Ok((fraction * 255.0).round() as u8)
+}
+
+/// Saturation and lightness are percentages, whether or not the `%` is written
+fn percent(arg: &Spanned<(f64, bool)>) -> Result<f64> {
+    let value = arg.value.0 / 100.0;
+    if !(0.0..=1.0).contains(&value) {
+        return Err(SyntaxError::Expected { expected: "a percentage from 0% to 100%", span: arg.span }.cut());
+    }
+    Ok(value)
+}
+
+fn hsl_to_rgb(hue: f64, saturation: f64, lightness: f64) -> [u8; 3] {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let sector = hue / 60.0;
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    [r, g, b].map(|c| ((c + m) * 255.0).round() as u8)
+}
+
+#[cfg(test)]
+mod test {
+    use winnow::Parser;
+
+    use crate::parser::span::Source;
+
+    use super::{color, Color};
+
+    fn parse(input: &str) -> Option<Color> {
+        color.parse_next(&mut Source::new(input).input()).ok()
+    }
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(Some(Color::rgb(255, 0, 0)), parse("#f00"));
+        assert_eq!(Some(Color { r: 255, g: 0, b: 0, a: 136 }), parse("#f008"));
+        assert_eq!(Some(Color::rgb(0x12, 0x34, 0x56)), parse("#123456"));
+        assert_eq!(Some(Color { r: 0x12, g: 0x34, b: 0x56, a: 0x78 }), parse("#12345678"));
+        assert_eq!(None, parse("#12345"));
+    }
+
+    #[test]
+    fn test_functions() {
+        assert_eq!(Some(Color::rgb(255, 128, 0)), parse("rgb(255, 128, 0)"));
+        assert_eq!(Some(Color::rgb(255, 128, 0)), parse("rgb(100%,50.2%,0%)"));
+        assert_eq!(Some(Color { r: 0, g: 0, b: 255, a: 128 }), parse("rgba(0, 0, 255, 0.5)"));
+        assert_eq!(Some(Color::rgb(255, 0, 0)), parse("hsl(0, 100%, 50%)"));
+        assert_eq!(Some(Color::rgb(0, 255, 0)), parse("hsl(480, 100%, 50%)"));
+        assert_eq!(Some(Color { r: 128, g: 128, b: 128, a: 64 }), parse("hsla(200, 0%, 50%, 25%)"));
+        assert_eq!(None, parse("rgb(256, 0, 0)"));
+        assert_eq!(None, parse("rgb(0, 0)"));
+    }
+
+    #[test]
+    fn test_named() {
+        assert_eq!(Some(Color::rgb(255, 165, 0)), parse("orange"));
+        assert_eq!(None, parse("bananas"));
+        assert_eq!(None, parse("green-ish"));
+        assert_eq!(None, parse("green()"));
+        assert_eq!(None, parse("redder"));
+    }
+
+    #[test]
+    fn test_linear() {
+        let [r, g, b, a] = Color { r: 255, g: 188, b: 0, a: 51 }.to_linear();
+        assert_eq!((1.0, 0.0, 0.2), (r, b, a));
+        // sRGB 188 is about half as bright in linear light
+        assert!((g - 0.5029).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,193 @@
+use std::{fmt, fs, io, path::{Path, PathBuf}, str::FromStr};
+
+use indexmap::IndexMap;
+use winnow::{ascii::{multispace0, space1, Caseless}, combinator::{alt, cut_err, opt, repeat}, error::ErrMode, stream::{Location, Stream}, token::{rest, take_till}, Parser};
+
+use super::{attributes, error::{self, Diagnostic, Result, SyntaxError}, ident::identifier, recover::node, span::{spanned, Input, Source, Span, Spanned}, tags::{comment, Node, Tag}, values::Value};
+
+/// A whole file: an optional prolog, then any number of root tags, with comments and whitespace all around
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Document {
+    pub prolog: Option<Prolog>,
+    pub roots: Vec<Node>,
+}
+
+/// The `<!doctype bftml version="1">` header a file may start with
+#[derive(Clone, Debug, PartialEq)]
+pub struct Prolog {
+    pub doctype: Spanned<String>,
+    pub attributes: IndexMap<Spanned<String>, Spanned<Value>>,
+}
+
+impl Document {
+    /// The root tags, leaving out comments and error nodes
+    pub fn tags(&self) -> impl Iterator<Item = &Tag> {
+        self.roots.iter().filter_map(|node| match node {
+            Node::Tag(tag) => Some(tag),
+            _ => None,
+        })
+    }
+
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        let path = path.as_ref().to_path_buf();
+        let text = fs::read_to_string(&path).map_err(|error| LoadError::Io { path: path.clone(), error })?;
+        text.parse().map_err(|error| LoadError::Syntax { path, text, error: Box::new(error) })
+    }
+}
+
+/// Why a document could not be loaded from a file
+#[derive(Debug)]
+pub enum LoadError {
+    Io { path: PathBuf, error: io::Error },
+    /// Keeps the text so the error can be rendered as a [`Report`](super::error::Report)
+    Syntax { path: PathBuf, text: String, error: Box<SyntaxError> },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io { path, error } => write!(f, "could not read {}: {error}", path.display()),
+            LoadError::Syntax { path, text, error } => write!(f, "{}", error.report(text).with_path(&path.display().to_string())),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io { error, .. } => Some(error),
+            LoadError::Syntax { error, .. } => Some(error.as_ref()),
+        }
+    }
+}
+
+pub fn document(s: &mut Input) -> Result<Document> {
+    let mut roots: Vec<Node> = repeat(0.., loose_comment).parse_next(s)?;
+    let _ = multispace0.parse_next(s)?;
+    let start = s.checkpoint();
+    let prolog = match opt(prolog).parse_next(s) {
+        Err(e @ ErrMode::Cut(_)) if s.state.recovery.is_some() => {
+            s.reset(&start);
+            // Only the prolog is broken, so the tags after it are still worth parsing
+            roots.push(skip(s, e, ('<', take_till(0.., '<')))?);
+            None
+        }
+        result => result?,
+    };
+    let keep = s.state.keep_comments;
+    let nodes: Vec<Node> = repeat(0.., alt((node, loose_comment))).parse_next(s)?;
+    roots.extend(nodes);
+    roots.retain(|node| keep || !matches!(node, Node::Comment(_)));
+
+    if let Err(e) = end(s) {
+        if s.state.recovery.is_none() {
+            return Err(e);
+        }
+        roots.push(skip(s, e, rest)?);
+    }
+    Ok(Document { prolog, roots })
+}
+
+/// A comment between roots. When recovering, one never closed takes the rest of the file with it, as an error node.
+fn loose_comment(s: &mut Input) -> Result<Node> {
+    let _ = multispace0.parse_next(s)?;
+    let start = s.checkpoint();
+    match comment.parse_next(s) {
+        Err(e @ ErrMode::Cut(_)) if s.state.recovery.is_some() => {
+            s.reset(&start);
+            skip(s, e, rest)
+        }
+        result => result.map(|c| Node::Comment(c.map(String::from))),
+    }
+}
+
+/// Records the error, then stands an error node in for the markup it broke on, as far as `extent` takes
+fn skip<'s, O>(s: &mut Input<'s>, error: ErrMode<SyntaxError>, extent: impl Parser<Input<'s>, O, ErrMode<SyntaxError>>) -> Result<Node> {
+    s.state.record(error::finish(error));
+    let skipped = spanned(extent.take()).parse_next(s)?;
+    Ok(Node::Error(skipped.map(String::from)))
+}
+
+fn prolog(s: &mut Input) -> Result<Prolog> {
+    let _ = (Caseless("<!doctype"), space1).parse_next(s)?;
+    let doctype = cut_err(spanned(identifier)).context("document type").parse_next(s)?.map(String::from);
+    let attributes = attributes::many.parse_next(s)?;
+    let _ = (multispace0, cut_err('>').context("`>`")).parse_next(s)?;
+    Ok(Prolog { doctype, attributes })
+}
+
+/// Makes sure nothing but whitespace and comments is left, so trailing markup is not silently dropped
+pub(crate) fn end(s: &mut Input) -> Result<()> {
+    let _: () = repeat(0.., (multispace0, comment).void()).parse_next(s)?;
+    let _ = multispace0.parse_next(s)?;
+    if s.is_empty() {
+        return Ok(());
+    }
+    let start = s.current_token_start();
+    let span: Span = s.state.span(start..start + s.trim_end().len());
+    Err(SyntaxError::TrailingInput { span }.cut())
+}
+
+impl FromStr for Document {
+    type Err = SyntaxError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        document(&mut Source::new(s).input()).map_err(error::finish)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parser::{error::SyntaxError, recover, span::Source, tags::{Node, Tag}, values::Value};
+
+    use super::{document, Document, LoadError};
+
+    #[test]
+    fn test_document() {
+        let text = "\n<!-- The counter -->\n<!DOCTYPE bftml version=1>\n<column />\n<!-- between -->\n<row />\n<!-- after -->\n";
+        let document: Document = text.parse().unwrap();
+        let prolog = document.prolog.unwrap();
+        assert_eq!("bftml", prolog.doctype.value);
+        assert_eq!(Value::Int(1), *prolog.attributes["version"]);
+        assert_eq!(vec![Node::Tag(Tag::new("column")), Node::Tag(Tag::new("row"))], document.roots);
+
+        assert_eq!(Document::default(), "  <!-- nothing -->  ".parse().unwrap());
+    }
+
+    #[test]
+    fn test_comments() {
+        let source = Source::new("<!-- a --><foo /><!-- b -->").keeping_comments();
+        let document = winnow::Parser::parse_next(&mut document, &mut source.input()).unwrap();
+        assert_eq!(3, document.roots.len());
+        assert_eq!(1, document.tags().count());
+    }
+
+    #[test]
+    fn test_trailing() {
+        let SyntaxError::TrailingInput { span } = "<foo />\nbananas \n".parse::<Document>().unwrap_err() else { panic!("Expected trailing input") };
+        assert_eq!(8..15, span.bytes());
+        assert!(matches!("<foo /> bananas".parse::<Tag>(), Err(SyntaxError::TrailingInput { .. })));
+        assert!("<foo /> <!-- fine -->\n".parse::<Tag>().is_ok());
+
+        let (document, errors) = recover::parse_document("<foo />\n<bar a=%% />\n</foo>");
+        assert_eq!(2, errors.len());
+        assert!(matches!(errors[1], SyntaxError::TrailingInput { .. }));
+        assert!(matches!(&document.roots[..], [Node::Tag(_), Node::Error(_), Node::Error(_)]));
+    }
+
+    #[test]
+    fn test_from_path() {
+        let document = Document::from_path(concat!(env!("CARGO_MANIFEST_DIR"), "/file.xml")).unwrap();
+        let column = document.tags().next().unwrap();
+        assert_eq!("column", column.name);
+        assert!(matches!(Document::from_path("missing.xml"), Err(LoadError::Io { .. })));
+
+        // Syntax errors are shown with the offending line
+        let path = std::env::temp_dir().join(format!("bftml-load-{}.bftml", std::process::id()));
+        std::fs::write(&path, "<column>\n    <row>\n</column>\n").unwrap();
+        let error = Document::from_path(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(error.to_string().contains(&format!("{}:3:", path.display())), "{error}");
+        assert!(error.to_string().contains("</column>"), "{error}");
+    }
+}
@@ -0,0 +1,280 @@
+use std::fmt;
+
+use winnow::{error::{AddContext, ErrMode, ParserError}, stream::{Location, Stream}};
+
+use super::{expr::ExprError, span::{Input, Span, Spanned}};
+
+/// Like winnow's own alias, but defaulting to our error type
+pub type Result<T, E = ErrMode<SyntaxError>> = std::result::Result<T, E>;
+
+/// Errors in the generic tag syntax, as opposed to tag errors about what a particular tag permits
+#[derive(Clone, Debug, PartialEq)]
+pub enum SyntaxError {
+    /// Catch-all for input the grammar does not allow at this point
+    Expected { expected: &'static str, span: Span },
+    UnclosedTag { name: String, span: Span },
+    MismatchedClose { open: Spanned<String>, close: Spanned<String> },
+    BadValue { key: String, span: Span },
+    /// An attribute given twice on one tag, where `second` is the later one
+    DuplicateAttribute { name: String, first: Span, second: Span },
+    DuplicatePreset { name: String, first: Span, second: Span },
+    MixedInner { name: String, span: Span },
+    BadEscape { span: Span },
+    NumberOutOfRange { span: Span },
+    /// Anything but whitespace and comments after the last tag
+    TrailingInput { span: Span },
+    Expr(ExprError),
+}
+
+/// A span to underline in a diagnostic, with a note on what is wrong there
+#[derive(Clone, Debug, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    pub primary: bool,
+}
+
+/// Anything that can be rendered as a [`Report`] against its source text
+pub trait Diagnostic {
+    fn message(&self) -> String;
+    fn labels(&self) -> Vec<Label>;
+
+    fn report<'a>(&'a self, source: &'a str) -> Report<'a, Self> where Self: Sized {
+        Report { diagnostic: self, source, path: None }
+    }
+}
+
+impl SyntaxError {
+    pub fn span(&self) -> Span {
+        match self {
+            SyntaxError::Expected { span, .. }
+            | SyntaxError::UnclosedTag { span, .. }
+            | SyntaxError::BadValue { span, .. }
+            | SyntaxError::MixedInner { span, .. }
+            | SyntaxError::BadEscape { span }
+            | SyntaxError::NumberOutOfRange { span }
+            | SyntaxError::TrailingInput { span } => *span,
+            SyntaxError::MismatchedClose { close, .. } => close.span,
+            SyntaxError::DuplicateAttribute { second, .. } | SyntaxError::DuplicatePreset { second, .. } => *second,
+            SyntaxError::Expr(error) => error.span(),
+        }
+    }
+
+    pub(crate) fn cut(self) -> ErrMode<Self> {
+        ErrMode::Cut(self)
+    }
+}
+
+impl Diagnostic for SyntaxError {
+    fn message(&self) -> String {
+        match self {
+            SyntaxError::Expected { expected: "", .. } => "unexpected input".into(),
+            SyntaxError::Expected { expected, .. } => format!("expected {expected}"),
+            SyntaxError::UnclosedTag { name, .. } => format!("unclosed tag `<{name}>`"),
+            SyntaxError::MismatchedClose { close, .. } => format!("mismatched closing tag `</{}>`", close.value),
+            SyntaxError::BadValue { key, .. } => format!("invalid value for attribute `{key}`"),
+            SyntaxError::DuplicateAttribute { name, .. } => format!("attribute `{name}` given more than once"),
+            SyntaxError::DuplicatePreset { name, .. } => format!("preset `{name}` given more than once"),
+            SyntaxError::MixedInner { name, .. } => format!("tag `<{name}>` mixes children and content"),
+            SyntaxError::BadEscape { .. } => "unknown escape sequence".into(),
+            SyntaxError::NumberOutOfRange { .. } => "number out of range".into(),
+            SyntaxError::TrailingInput { .. } => "unexpected input after the last tag".into(),
+            SyntaxError::Expr(error) => error.message(),
+        }
+    }
+
+    fn labels(&self) -> Vec<Label> {
+        let primary = |span, message: &str| Label { span, message: message.into(), primary: true };
+        match self {
+            SyntaxError::Expected { span, .. } => vec![primary(*span, "")],
+            SyntaxError::UnclosedTag { span, .. } => vec![primary(*span, "this tag is never closed")],
+            SyntaxError::MismatchedClose { open, close } => vec![
+                Label { span: open.span, message: "opened here".into(), primary: false },
+                primary(close.span, &format!("expected `</{}>`", open.value)),
+            ],
+            SyntaxError::BadValue { span, .. } => vec![primary(*span, "not a valid value")],
+            SyntaxError::DuplicateAttribute { first, second, .. } | SyntaxError::DuplicatePreset { first, second, .. } => vec![
+                Label { span: *first, message: "first given here".into(), primary: false },
+                primary(*second, "given again here"),
+            ],
+            SyntaxError::MixedInner { span, .. } => vec![primary(*span, "children and content cannot be mixed")],
+            SyntaxError::BadEscape { span } => vec![primary(*span, "")],
+            SyntaxError::NumberOutOfRange { span } => vec![primary(*span, "does not fit in 64 bits")],
+            SyntaxError::TrailingInput { span } => vec![primary(*span, "expected a tag, a comment or the end of the file")],
+            SyntaxError::Expr(error) => error.labels(),
+        }
+    }
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.message(), self.span())
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+impl<'s> ParserError<Input<'s>> for SyntaxError {
+    type Inner = Self;
+
+    fn from_input(input: &Input<'s>) -> Self {
+        let start = input.current_token_start();
+        let end = start + input.peek_token().map_or(0, char::len_utf8);
+        SyntaxError::Expected { expected: "", span: input.state.span(start..end) }
+    }
+
+    /// Report whichever alternative got furthest, as it is most likely what was meant
+    fn or(self, other: Self) -> Self {
+        if self.span().start.offset > other.span().start.offset { self } else { other }
+    }
+
+    fn into_inner(self) -> Result<Self::Inner, Self> {
+        Ok(self)
+    }
+}
+
+impl<'s> AddContext<Input<'s>> for SyntaxError {
+    /// Only the innermost context is kept, as it is the most specific
+    fn add_context(self, _input: &Input<'s>, _token_start: &<Input<'s> as Stream>::Checkpoint, context: &'static str) -> Self {
+        match self {
+            SyntaxError::Expected { expected: "", span } => SyntaxError::Expected { expected: context, span },
+            other => other,
+        }
+    }
+}
+
+/// Unwraps the error from a parser run over a complete input, which can never be incomplete
+pub(crate) fn finish(e: ErrMode<SyntaxError>) -> SyntaxError {
+    e.into_inner().unwrap_or_else(|_| unreachable!("Input is never partial"))
+}
+
+/// Renders a diagnostic in the style of rustc, quoting each labelled line of the source
+pub struct Report<'a, D> {
+    diagnostic: &'a D,
+    source: &'a str,
+    path: Option<&'a str>,
+}
+
+impl<'a, D> Report<'a, D> {
+    /// Names the file the source came from, ahead of the line and column
+    pub fn with_path(mut self, path: &'a str) -> Self {
+        self.path = Some(path);
+        self
+    }
+}
+
+impl<D: Diagnostic> fmt::Display for Report<'_, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut labels = self.diagnostic.labels();
+        // Trees built in code have default spans, on line 0, which point nowhere in the source
+        labels.retain(|label| label.span.start.line > 0);
+        labels.sort_by_key(|label| label.span.start.offset);
+        let width = labels.iter().map(|l| l.span.start.line.to_string().len()).max().unwrap_or(1);
+        let gutter = " ".repeat(width);
+
+        writeln!(f, "error: {}", self.diagnostic.message())?;
+        if labels.is_empty() {
+            return Ok(());
+        }
+        if let Some(label) = labels.iter().find(|l| l.primary).or(labels.first()) {
+            let path = self.path.map(|path| format!("{path}:")).unwrap_or_default();
+            writeln!(f, "{gutter}--> {path}{}", label.span)?;
+        }
+        writeln!(f, "{gutter} |")?;
+
+        let mut previous = None;
+        for label in &labels {
+            let line = label.span.start.line;
+            let text = self.source.lines().nth(line as usize - 1).unwrap_or("");
+            if previous != Some(line) {
+                writeln!(f, "{line:>width$} | {text}")?;
+                previous = Some(line);
+            }
+            // Spans running past the line are only underlined up to its end
+            let indent = label.span.start.column as usize - 1;
+            let length = if label.span.end.line == line {
+                (label.span.end.column - label.span.start.column) as usize
+            } else {
+                text.chars().count().saturating_sub(indent)
+            };
+            let marker = if label.primary { "^" } else { "-" };
+            let underline = marker.repeat(length.max(1));
+            let line = format!("{gutter} | {}{underline} {}", " ".repeat(indent), label.message);
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parser::{span::Span, tags::Tag};
+
+    use super::{Diagnostic, SyntaxError};
+
+    fn error(input: &str) -> SyntaxError {
+        input.parse::<Tag>().unwrap_err()
+    }
+
+    #[test]
+    fn test_unclosed() {
+        assert!(matches!(error("<foo><bar /></foo"), SyntaxError::Expected { expected: "`>`", .. }));
+        let SyntaxError::UnclosedTag { name, span } = error("<foo><bar />") else { panic!("Expected unclosed tag") };
+        assert_eq!("foo", name);
+        assert_eq!(0..5, span.bytes());
+    }
+
+    #[test]
+    fn test_mismatched() {
+        let SyntaxError::MismatchedClose { open, close } = error("<bar></foo>") else { panic!("Expected mismatched tag") };
+        assert_eq!(("bar", "foo"), (open.value.as_str(), close.value.as_str()));
+        assert_eq!(7..10, close.span.bytes());
+    }
+
+    #[test]
+    fn test_bad_value() {
+        let SyntaxError::BadValue { key, span } = error("<foo a=1 b=%% />") else { panic!("Expected bad value") };
+        assert_eq!("b", key);
+        assert_eq!(11..13, span.bytes());
+    }
+
+    #[test]
+    fn test_duplicate() {
+        let SyntaxError::DuplicateAttribute { name, first, second } = error("<box color=#f00 color=#0f0 />") else { panic!("Expected a duplicate attribute") };
+        assert_eq!("color", name);
+        assert_eq!((5..10, 16..21), (first.bytes(), second.bytes()));
+        let SyntaxError::DuplicatePreset { first, second, .. } = error("<box rounded hidden rounded />") else { panic!("Expected a duplicate preset") };
+        assert_eq!((5..12, 20..27), (first.bytes(), second.bytes()));
+        assert!("<box rounded rounded=1 />".parse::<Tag>().is_ok());
+
+        let (_, errors) = crate::parser::recover::parse("<row><box a=1 a=2 /><box /></row>");
+        assert!(matches!(errors[..], [SyntaxError::DuplicateAttribute { .. }]));
+    }
+
+    #[test]
+    fn test_mixed() {
+        assert!(matches!(error("<foo>Bananas<bar /></foo>"), SyntaxError::MixedInner { .. }));
+        assert!(matches!(error("<foo><bar />Bananas</foo>"), SyntaxError::MixedInner { .. }));
+    }
+
+    #[test]
+    fn test_report() {
+        let source = "<column>\n    <bar></foo>\n</column>";
+        let report = error(source).report(source).to_string();
+        let expected = "\
+error: mismatched closing tag `</foo>`
+ --> 2:12
+  |
+2 |     <bar></foo>
+  |      --- opened here
+  |            ^^^ expected `</bar>`
+";
+        assert_eq!(expected, report);
+        let report = error(source).report(source).with_path("file.xml").to_string();
+        assert_eq!(expected.replace(" --> ", " --> file.xml:"), report);
+
+        // Nothing is quoted for spans that point nowhere
+        let error = SyntaxError::UnclosedTag { name: "foo".into(), span: Span::default() };
+        assert_eq!("error: unclosed tag `<foo>`\n", error.report(source).to_string());
+    }
+}
@@ -0,0 +1,138 @@
+//! Quoted strings take backslash escapes: `\"`, `\\`, `\n`, `\r`, `\t`, `\0` and `\u{...}`.
+//! Content takes entities instead, as a backslash is common in prose: `&lt;`, `&gt;`, `&amp;`,
+//! `&quot;`, `&apos;`, and code points as `&#60;` or `&#x3C;`.
+//! Anything else after a `\` or `&` is a syntax error rather than being taken literally.
+
+use std::ops::Range;
+
+use winnow::{combinator::{alt, delimited}, error::ErrMode, stream::{Location, Stream}, token::take_while, Parser};
+
+use super::{error::{Result, SyntaxError}, span::Input};
+
+pub fn backslash(s: &mut Input) -> Result<char> {
+    let _ = '\\'.parse_next(s)?;
+    escaped(s, alt((
+        'n'.value('\n'),
+        'r'.value('\r'),
+        't'.value('\t'),
+        '0'.value('\0'),
+        '\\'.value('\\'),
+        '"'.value('"'),
+        delimited("u{", code_point(16), '}'),
+    )))
+}
+
+pub fn entity(s: &mut Input) -> Result<char> {
+    let _ = '&'.parse_next(s)?;
+    escaped(s, alt((
+        "lt;".value('<'),
+        "gt;".value('>'),
+        "amp;".value('&'),
+        "quot;".value('"'),
+        "apos;".value('\''),
+        delimited("#x", code_point(16), ';'),
+        delimited('#', code_point(10), ';'),
+    )))
+}
+
+/// Runs the part after the `\` or `&`, blaming the whole sequence if it is not valid
+fn escaped<'s>(s: &mut Input<'s>, mut sequence: impl Parser<Input<'s>, char, ErrMode<SyntaxError>>) -> Result<char> {
+    let start = s.previous_token_end() - 1;
+    sequence.parse_next(s).map_err(|_| {
+        let end = s.current_token_start() + s.peek_token().map_or(0, char::len_utf8);
+        SyntaxError::BadEscape { span: s.state.span(start..end) }.cut()
+    })
+}
+
+fn code_point<'s>(radix: u32) -> impl Parser<Input<'s>, char, ErrMode<SyntaxError>> {
+    take_while(1..=8, move |c: char| c.is_digit(radix))
+        .verify_map(move |digits: &str| u32::from_str_radix(digits, radix).ok().and_then(char::from_u32))
+}
+
+/// The reverse of the escapes taken by quoted strings, without the quotes themselves
+pub fn string(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '\0' => escaped.push_str("\\0"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The reverse of the entities taken by content
+pub fn content(text: &str) -> String {
+    collapsed(text, 0..text.len(), true)
+}
+
+/// Like [`content`] for the part of `text` in `range`, but unless `preserve` is set, also writing whitespace
+/// as an entity wherever it would otherwise be collapsed. That depends on the characters around it in all of `text`.
+pub fn collapsed(text: &str, range: Range<usize>, preserve: bool) -> String {
+    let word = |c: Option<char>| c.is_some_and(|c| !c.is_whitespace());
+    let mut escaped = String::with_capacity(range.len());
+    for (i, c) in text[range.clone()].char_indices() {
+        let i = range.start + i;
+        let between = word(text[..i].chars().next_back()) && word(text[i + c.len_utf8()..].chars().next());
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            ' ' if preserve || between => escaped.push(' '),
+            c if c.is_whitespace() && !preserve => escaped.push_str(&format!("&#x{:X};", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use winnow::Parser;
+
+    use crate::parser::{error::{Result, SyntaxError}, span::Source};
+
+    use super::{backslash, collapsed, content, entity, string};
+
+    #[test]
+    fn test_backslash() {
+        for (input, expected) in [("\\n", '\n'), ("\\\"", '"'), ("\\\\", '\\'), ("\\u{1F600}", '😀')] {
+            assert_eq!(expected, backslash.parse_next(&mut Source::new(input).input()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_entity() {
+        for (input, expected) in [("&lt;", '<'), ("&amp;", '&'), ("&#60;", '<'), ("&#x1F600;", '😀')] {
+            assert_eq!(expected, entity.parse_next(&mut Source::new(input).input()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_bad_escape() {
+        let source = Source::new("ab\\q");
+        let mut input = source.input();
+        let _: Result<&str> = "ab".parse_next(&mut input);
+        let error = backslash.parse_next(&mut input).unwrap_err().into_inner().unwrap();
+        assert_eq!(SyntaxError::BadEscape { span: source.span(2..4) }, error);
+
+        let source = Source::new("&nbsp;");
+        assert!(entity.parse_next(&mut source.input()).is_err());
+        let source = Source::new("\\u{110000}");
+        assert!(backslash.parse_next(&mut source.input()).is_err());
+    }
+
+    #[test]
+    fn test_reverse() {
+        assert_eq!("say \\\"hi\\\"\\n\\\\", string("say \"hi\"\n\\"));
+        assert_eq!("a &lt;b&gt; &amp;c", content("a <b> &c"));
+        assert_eq!("&#x20;a b&#x20;&#x20;c&#xA;", collapsed(" a b  c\n", 0..8, false));
+        assert_eq!("b&#x20;", collapsed(" a b  c\n", 3..5, false));
+    }
+}
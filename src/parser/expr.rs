@@ -0,0 +1,221 @@
+//! Attribute values worked out at runtime rather than written out, so they can be bound to state.
+//! Braces hold a reference to a signal such as `{counter}` or `{user.name}`, or any other expression.
+//! Calls such as `increment()` or `clamp(x, 0, 10)` can also go without braces.
+
+use std::fmt;
+
+use winnow::{ascii::multispace0, combinator::{alt, opt, separated}, error::ErrMode, stream::{Location, Stream}, token::take_till, Parser};
+
+use super::{error::{Label, Result, SyntaxError}, ident, span::{spanned, Input, Span, Spanned}, values::{value, Value}};
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
+pub enum Expr {
+    /// A signal, or a field of one, as a path such as `user.name`
+    Ref(Vec<Spanned<String>>),
+    Call { function: Vec<Spanned<String>>, args: Vec<Spanned<Expr>> },
+    Literal(Value),
+}
+
+/// Errors in an expression, which show up as [`SyntaxError::Expr`]
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExprError {
+    Empty { span: Span },
+    /// Something that is neither a reference, a call nor a literal
+    Invalid { span: Span },
+    UnclosedBrace { span: Span },
+    UnclosedCall { function: String, span: Span },
+}
+
+impl ExprError {
+    pub fn span(&self) -> Span {
+        match self {
+            ExprError::Empty { span }
+            | ExprError::Invalid { span }
+            | ExprError::UnclosedBrace { span }
+            | ExprError::UnclosedCall { span, .. } => *span,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            ExprError::Empty { .. } => "empty expression".into(),
+            ExprError::Invalid { .. } => "invalid expression".into(),
+            ExprError::UnclosedBrace { .. } => "unclosed `{`".into(),
+            ExprError::UnclosedCall { function, .. } => format!("unclosed call to `{function}`"),
+        }
+    }
+
+    pub fn labels(&self) -> Vec<Label> {
+        let message = match self {
+            ExprError::Empty { .. } => "expected a reference, call or literal between the braces",
+            ExprError::Invalid { .. } => "expected a reference, call or literal",
+            ExprError::UnclosedBrace { .. } | ExprError::UnclosedCall { .. } => "never closed",
+        };
+        vec![Label { span: self.span(), message: message.into(), primary: true }]
+    }
+
+    fn cut(self) -> ErrMode<SyntaxError> {
+        SyntaxError::Expr(self).cut()
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = |f: &mut fmt::Formatter<'_>, path: &[Spanned<String>]| {
+            let path: Vec<&str> = path.iter().map(|name| name.as_str()).collect();
+            write!(f, "{}", path.join("."))
+        };
+        match self {
+            Expr::Ref(names) => path(f, names),
+            Expr::Call { function, args } => {
+                path(f, function)?;
+                write!(f, "(")?;
+                for (i, arg) in args.iter().enumerate() {
+                    write!(f, "{}{}", if i == 0 { "" } else { ", " }, arg.value)?;
+                }
+                write!(f, ")")
+            }
+            Expr::Literal(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+/// An expression in braces, or a bare call
+pub fn expression(s: &mut Input) -> Result<Expr> {
+    alt((braced, call)).parse_next(s)
+}
+
+fn braced(s: &mut Input) -> Result<Expr> {
+    let open = spanned('{').parse_next(s)?;
+    let _ = multispace0.parse_next(s)?;
+    if let Some(close) = opt(spanned('}')).parse_next(s)? {
+        return Err(ExprError::Empty { span: Span { start: open.span.start, end: close.span.end } }.cut());
+    }
+    let result = expr.parse_next(s).map_err(|e| match e {
+        ErrMode::Backtrack(_) => ExprError::Invalid { span: blame(s) }.cut(),
+        e => e,
+    })?;
+    let _ = multispace0.parse_next(s)?;
+    let _ = '}'.parse_next(s).map_err(|_: ErrMode<SyntaxError>| match blame(s) {
+        span if span.bytes().is_empty() => ExprError::UnclosedBrace { span: open.span }.cut(),
+        span => ExprError::Invalid { span }.cut(),
+    })?;
+    Ok(result)
+}
+
+/// Names are reserved for references inside expressions, so `{red}` is a signal rather than a color
+fn expr(s: &mut Input) -> Result<Expr> {
+    alt((call, path.map(Expr::Ref), value.map(Expr::Literal))).parse_next(s)
+}
+
+fn call(s: &mut Input) -> Result<Expr> {
+    let function = path.parse_next(s)?;
+    let open = spanned('(').parse_next(s)?;
+    let separator = || (multispace0, ',', multispace0);
+    let _ = multispace0.parse_next(s)?;
+    let args = separated(0.., spanned(expr), separator()).parse_next(s)?;
+    let _ = opt(separator()).parse_next(s)?;
+    let _ = (multispace0, ')').parse_next(s).map_err(|_: ErrMode<SyntaxError>| match blame(s) {
+        span if span.bytes().is_empty() => {
+            let function = function.iter().map(|name| name.as_str()).collect::<Vec<_>>().join(".");
+            ExprError::UnclosedCall { function, span: open.span }.cut()
+        }
+        span => ExprError::Invalid { span }.cut(),
+    })?;
+    Ok(Expr::Call { function, args })
+}
+
+/// Names separated by `.`, where `true` and `false` are left to be booleans
+fn path(s: &mut Input) -> Result<Vec<Spanned<String>>> {
+    separated(1.., name, '.')
+        .verify(|path: &Vec<Spanned<String>>| !matches!(&path[..], [name] if name.as_str() == "true" || name.as_str() == "false"))
+        .parse_next(s)
+}
+
+/// Named like attributes, so a signal can be `{min-width}`
+fn name(s: &mut Input) -> Result<Spanned<String>> {
+    spanned(ident::name.map(String::from)).parse_next(s)
+}
+
+/// The run of input to blame for an invalid expression, which is empty where the expression just stops
+fn blame(s: &mut Input) -> Span {
+    let start = s.checkpoint();
+    let run: Result<_> = spanned(take_till(0.., |c: char| c.is_whitespace() || matches!(c, ',' | ')' | '}' | '>'))).parse_next(s);
+    s.reset(&start);
+    let run = run.unwrap_or_else(|_| unreachable!("Taking zero or more never fails"));
+    match s.peek_token() {
+        // Point at the one character that cannot start an expression
+        Some(c) if run.span.bytes().is_empty() && c != '}' && c != ')' => {
+            let start = s.current_token_start();
+            s.state.span(start..start + c.len_utf8())
+        }
+        _ => run.span,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use winnow::Parser;
+
+    use crate::parser::{error::SyntaxError, span::Source, values::Value};
+
+    use super::{expression, Expr, ExprError};
+
+    fn parse(input: &str) -> Result<Expr, SyntaxError> {
+        expression.parse_next(&mut Source::new(input).input()).map_err(|e| e.into_inner().unwrap())
+    }
+
+    fn names(path: &[&str]) -> Vec<crate::parser::span::Spanned<String>> {
+        path.iter().map(|&name| name.into()).collect()
+    }
+
+    #[test]
+    fn test_reference() {
+        assert_eq!(Expr::Ref(names(&["counter"])), parse("{counter}").unwrap());
+        assert_eq!(Expr::Ref(names(&["user", "name"])), parse("{ user.name }").unwrap());
+        assert_eq!(Expr::Ref(names(&["red"])), parse("{red}").unwrap());
+        assert_eq!(Expr::Ref(names(&["min-width"])), parse("{min-width}").unwrap());
+        assert_eq!(Expr::Ref(names(&["theme", "on-primary"])), parse("{theme.on-primary}").unwrap());
+        assert_eq!(Expr::Literal(Value::Bool(true)), parse("{true}").unwrap());
+    }
+
+    #[test]
+    fn test_call() {
+        assert_eq!(Expr::Call { function: names(&["increment"]), args: vec![] }, parse("increment()").unwrap());
+        let Expr::Call { function, args } = parse("{counter.set(clamp(x, 0, 10), \"a\",)}").unwrap() else { panic!("Expected a call") };
+        assert_eq!(names(&["counter", "set"]), function);
+        assert_eq!(Expr::Literal(Value::String("a".into())), args[1].value);
+        let Expr::Call { args, .. } = &args[0].value else { panic!("Expected a nested call") };
+        assert_eq!(Expr::Ref(names(&["x"])), args[0].value);
+        assert_eq!(Expr::Literal(Value::Int(10)), args[2].value);
+        assert_eq!(19..20, args[0].span.bytes());
+    }
+
+    #[test]
+    fn test_errors() {
+        let expr = |input| match parse(input) {
+            Err(SyntaxError::Expr(e)) => e,
+            other => panic!("Expected an expression error, got {other:?}"),
+        };
+        assert_eq!(0..4, expr("{  }").span().bytes());
+        assert!(matches!(expr("{ } "), ExprError::Empty { .. }));
+        assert!(matches!(expr("{counter"), ExprError::UnclosedBrace { .. }));
+        assert!(matches!(expr("f(1, 2"), ExprError::UnclosedCall { .. }));
+        let error = expr("{%%}");
+        assert!(matches!(error, ExprError::Invalid { .. }));
+        assert_eq!(1..3, error.span().bytes());
+        assert_eq!(5..7, expr("f(1, %%)").span().bytes());
+        assert_eq!(3..4, expr("{a b}").span().bytes());
+        assert!(parse("f").is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        for input in ["{counter}", "{user.name}", "increment()", "{f(x, 1.5, \"a\", [1, 2], g())}", "{-1}"] {
+            let parsed = parse(input).unwrap();
+            assert_eq!(parsed, parse(&format!("{{{parsed}}}")).unwrap());
+        }
+        assert_eq!("f(x, 1)", parse("f( x,1 )").unwrap().to_string());
+    }
+}
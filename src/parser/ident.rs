@@ -0,0 +1,87 @@
+//! Tag names, traits and attribute keys all share one grammar: a name, optionally behind a namespace as in `ui:row`.
+//! Names start with a letter or `_`, and carry on with letters, digits, `_` and `-`, as in `my-button` or `aria-label`.
+//! Letters are ASCII only, unless the `unicode` feature is enabled to allow `XID_Start` and `XID_Continue` like Rust does.
+
+use winnow::{combinator::opt, token::{one_of, take_while}, Parser};
+
+use super::{error::Result, span::Input};
+
+#[cfg(not(feature = "unicode"))]
+pub fn is_start(c: char) -> bool {
+    c == '_' || c.is_ascii_alphabetic()
+}
+
+#[cfg(not(feature = "unicode"))]
+pub fn is_continue(c: char) -> bool {
+    c == '-' || c == '_' || c.is_ascii_alphanumeric()
+}
+
+#[cfg(feature = "unicode")]
+pub fn is_start(c: char) -> bool {
+    c == '_' || unicode_ident::is_xid_start(c)
+}
+
+#[cfg(feature = "unicode")]
+pub fn is_continue(c: char) -> bool {
+    c == '-' || unicode_ident::is_xid_continue(c)
+}
+
+/// A single name, without a namespace
+pub fn name<'s>(s: &mut Input<'s>) -> Result<&'s str> {
+    (one_of(is_start), take_while(0.., is_continue)).take().parse_next(s)
+}
+
+/// A name with an optional namespace, as in `ui:row`
+pub fn identifier<'s>(s: &mut Input<'s>) -> Result<&'s str> {
+    (name, opt((':', name))).take().parse_next(s)
+}
+
+/// Whether the whole of `text` is an identifier
+pub fn is_identifier(text: &str) -> bool {
+    let (namespace, name) = split(text);
+    let valid = |name: &str| name.chars().next().is_some_and(is_start) && name.chars().all(is_continue);
+    namespace.is_none_or(valid) && valid(name)
+}
+
+/// Splits an identifier into its namespace, if any, and its name
+pub fn split(identifier: &str) -> (Option<&str>, &str) {
+    match identifier.split_once(':') {
+        Some((namespace, name)) => (Some(namespace), name),
+        None => (None, identifier),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use winnow::Parser;
+
+    use crate::parser::span::Source;
+
+    use super::{identifier, is_identifier, split};
+
+    fn parse(input: &str) -> Option<&str> {
+        let source = Source::new(input);
+        identifier.parse_next(&mut source.input()).ok().map(|parsed| &input[..parsed.len()])
+    }
+
+    #[test]
+    fn test_identifier() {
+        for input in ["row", "my-button", "on_click", "aria-label", "_private", "ui:row", "h1"] {
+            assert_eq!(Some(input), parse(input));
+        }
+        assert_eq!(Some("ui"), parse("ui:"));
+        assert_eq!(Some("a:b"), parse("a:b:c"));
+        assert_eq!(None, parse("1st"));
+        assert_eq!(None, parse("-x"));
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!((Some("ui"), "row"), split("ui:row"));
+        assert_eq!((None, "row"), split("row"));
+        assert!(is_identifier("ui:my-row"));
+        assert!(!is_identifier("ui:"));
+        assert!(!is_identifier("a b"));
+        assert!(!is_identifier("a:b:c"));
+    }
+}
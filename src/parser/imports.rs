@@ -0,0 +1,385 @@
+//! Documents spread over several files, joined by `<import path="./buttons.bftml" />`.
+//! Paths are relative to the file doing the importing. Loading a file loads everything it imports in turn,
+//! and stitching puts the roots of each imported file where its `<import>` was, giving one static tree.
+
+use std::{fmt, fs, io, path::{Component, Path, PathBuf}};
+
+use indexmap::IndexMap;
+
+use super::{document::Document, error::{Diagnostic, Label, SyntaxError}, incremental::Path as NodePath, recover, span::Span, tags::{Inner, Node, Tag}};
+
+pub const IMPORT: &str = "import";
+
+/// Every file reached from the root through imports, each known by one path however it is reached
+#[derive(Debug, Default)]
+pub struct Graph {
+    /// The root file comes first, then the others in the order they were first imported
+    pub modules: IndexMap<PathBuf, Module>,
+}
+
+#[derive(Debug)]
+pub struct Module {
+    /// Kept so errors within the file can be rendered as [`Report`](super::error::Report)s
+    pub text: String,
+    /// With error nodes standing in for any markup with syntax errors
+    pub document: Document,
+    /// Each file imported, along with where the `<import>` is
+    pub imports: Vec<(PathBuf, Span)>,
+}
+
+/// The tree with every import stitched in
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Stitched {
+    pub document: Document,
+    pub root: PathBuf,
+    /// The file of each node brought in from elsewhere, by its path in the document.
+    /// Everything within such a node comes from the same file unless listed too.
+    pub files: IndexMap<NodePath, PathBuf>,
+}
+
+impl Stitched {
+    /// The file a node came from, and so what its spans point into
+    pub fn file(&self, path: &[usize]) -> &Path {
+        (0..=path.len()).rev().find_map(|depth| self.files.get(&path[..depth])).unwrap_or(&self.root)
+    }
+}
+
+impl Graph {
+    /// Loads the file at `path` and everything it imports, carrying on past errors to find them all
+    pub fn load(path: impl AsRef<Path>) -> (Graph, Vec<ImportError>) {
+        let mut loader = Loader { graph: Graph::default(), errors: Vec::new(), stack: Vec::new() };
+        loader.visit(normalize(path.as_ref()), None);
+        (loader.graph, loader.errors)
+    }
+
+    pub fn text(&self, file: &Path) -> Option<&str> {
+        self.modules.get(file).map(|module| module.text.as_str())
+    }
+
+    /// One tree, with each import replaced by the roots of the file it names.
+    /// A file imported twice appears twice, while imports that failed to load are left out.
+    pub fn stitch(&self) -> Stitched {
+        let Some((root, module)) = self.modules.first() else { return Stitched::default() };
+        let mut stitched = Stitched { document: Document { prolog: module.document.prolog.clone(), roots: Vec::new() }, root: root.clone(), files: IndexMap::new() };
+        let mut roots = Vec::new();
+        self.splice(root, &module.document.roots, (&[], root), &mut roots, &mut stitched.files, &mut vec![root.clone()]);
+        stitched.document.roots = roots;
+        stitched
+    }
+
+    /// Copies `nodes` from `file` onto the end of `out`, within a parent at `path` from the file `context`
+    fn splice(&self, file: &Path, nodes: &[Node], (path, context): (&[usize], &Path), out: &mut Vec<Node>, files: &mut IndexMap<NodePath, PathBuf>, stack: &mut Vec<PathBuf>) {
+        for node in nodes {
+            if let Node::Tag(tag) = node {
+                if tag.name == IMPORT {
+                    // Goes by the imports found while loading, so both agree on every path
+                    let imports = self.modules.get(file).map_or(&[][..], |module| &module.imports);
+                    let Some((target, _)) = imports.iter().find(|(_, span)| *span == tag.span) else { continue };
+                    let Some(module) = self.modules.get(target) else { continue };
+                    // Only reachable after a cycle was reported
+                    if stack.contains(target) {
+                        continue;
+                    }
+                    stack.push(target.clone());
+                    self.splice(target, &module.document.roots, (path, context), out, files, stack);
+                    stack.pop();
+                    continue;
+                }
+            }
+
+            let mut at = path.to_vec();
+            at.push(out.len());
+            if file != context {
+                files.insert(at.clone(), file.to_path_buf());
+            }
+            out.push(match node {
+                Node::Tag(tag) => {
+                    let inner = match &tag.inner {
+                        Inner::Children(children) => {
+                            let mut spliced = Vec::new();
+                            self.splice(file, children, (&at, file), &mut spliced, files, stack);
+                            Inner::Children(spliced)
+                        }
+                        other => other.clone(),
+                    };
+                    Node::Tag(Tag { name: tag.name.clone(), traits: tag.traits.clone(), attributes: tag.attributes.clone(), inner, comments: tag.comments.clone(), span: tag.span })
+                }
+                other => other.clone(),
+            });
+        }
+    }
+}
+
+struct Loader {
+    graph: Graph,
+    errors: Vec<ImportError>,
+    /// The files being loaded, each imported by the one before
+    stack: Vec<PathBuf>,
+}
+
+impl Loader {
+    fn visit(&mut self, path: PathBuf, import: Option<(PathBuf, Span)>) {
+        if let Some(i) = self.stack.iter().position(|file| *file == path) {
+            let mut chain = self.stack[i..].to_vec();
+            chain.push(path.clone());
+            // The stack starts out empty, so there is always an import here, but the root file will do otherwise
+            let (file, span) = import.unwrap_or((path, Span::default()));
+            self.errors.push(ImportError::Cycle { file, span, chain });
+            return;
+        }
+        if self.graph.modules.contains_key(&path) {
+            return;
+        }
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(error) => {
+                self.errors.push(ImportError::Io { path, error, import });
+                return;
+            }
+        };
+        let (document, errors) = recover::parse_document(&text);
+        self.errors.extend(errors.into_iter().map(|error| ImportError::Syntax { file: path.clone(), error }));
+        let mut imports = Vec::new();
+        for node in &document.roots {
+            if let Node::Tag(tag) = node {
+                self.imports(&path, tag, &mut imports);
+            }
+        }
+        self.graph.modules.insert(path.clone(), Module { text, document, imports: imports.clone() });
+
+        self.stack.push(path.clone());
+        for (target, span) in imports {
+            self.visit(target, Some((path.clone(), span)));
+        }
+        self.stack.pop();
+    }
+
+    fn imports(&mut self, file: &Path, tag: &Tag, imports: &mut Vec<(PathBuf, Span)>) {
+        if tag.name == IMPORT {
+            match target(file, tag) {
+                Some(target) => imports.push((target, tag.span)),
+                None => self.errors.push(ImportError::BadImport { file: file.to_path_buf(), span: tag.span }),
+            }
+            return;
+        }
+        if let Inner::Children(children) = &tag.inner {
+            for node in children {
+                if let Node::Tag(child) = node {
+                    self.imports(file, child, imports);
+                }
+            }
+        }
+    }
+}
+
+/// The file an `<import>` in `file` names
+fn target(file: &Path, import: &Tag) -> Option<PathBuf> {
+    let relative = import.attributes.get("path")?.as_str()?;
+    Some(normalize(&file.parent().unwrap_or(Path::new("")).join(relative)))
+}
+
+/// Resolves `.` and `..` without going to the file system
+fn normalize(path: &Path) -> PathBuf {
+    let mut normal = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(normal.components().next_back(), Some(Component::Normal(_))) => {
+                normal.pop();
+            }
+            other => normal.push(other),
+        }
+    }
+    normal
+}
+
+/// Errors in loading a file and its imports, each naming the file its span points into
+#[derive(Debug)]
+pub enum ImportError {
+    /// `import` is where the file was imported, which is `None` for the root file
+    Io { path: PathBuf, error: io::Error, import: Option<(PathBuf, Span)> },
+    Syntax { file: PathBuf, error: SyntaxError },
+    /// An `<import>` without a `path` string
+    BadImport { file: PathBuf, span: Span },
+    /// Files importing each other in a loop, from the first file back to itself.
+    /// `span` is the `<import>` that closes the loop.
+    Cycle { file: PathBuf, span: Span, chain: Vec<PathBuf> },
+}
+
+impl ImportError {
+    /// The file the error is in, and so which text to render it against
+    pub fn file(&self) -> Option<&Path> {
+        match self {
+            ImportError::Io { import, .. } => import.as_ref().map(|(file, _)| file.as_path()),
+            ImportError::Syntax { file, .. } | ImportError::BadImport { file, .. } | ImportError::Cycle { file, .. } => Some(file),
+        }
+    }
+}
+
+impl Diagnostic for ImportError {
+    fn message(&self) -> String {
+        match self {
+            ImportError::Io { path, error, .. } => format!("could not read {}: {error}", path.display()),
+            ImportError::Syntax { error, .. } => error.message(),
+            ImportError::BadImport { .. } => format!("`<{IMPORT}>` needs a `path` string"),
+            ImportError::Cycle { chain, .. } => {
+                let chain: Vec<String> = chain.iter().map(|file| file.display().to_string()).collect();
+                format!("files import each other in a cycle: {}", chain.join(" -> "))
+            }
+        }
+    }
+
+    fn labels(&self) -> Vec<Label> {
+        let primary = |span, message: &str| vec![Label { span, message: message.into(), primary: true }];
+        match self {
+            ImportError::Io { import: Some((_, span)), .. } => primary(*span, "imported here"),
+            ImportError::Io { import: None, .. } => Vec::new(),
+            ImportError::Syntax { error, .. } => error.labels(),
+            ImportError::BadImport { span, .. } => primary(*span, ""),
+            ImportError::Cycle { span, .. } => primary(*span, "this import closes the cycle"),
+        }
+    }
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.file(), self.labels().first()) {
+            (Some(file), Some(label)) => write!(f, "{}:{}: {}", file.display(), label.span, self.message()),
+            _ => write!(f, "{}", self.message()),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ImportError::Io { error, .. } => Some(error),
+            ImportError::Syntax { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, path::{Path, PathBuf}};
+
+    use crate::parser::{error::{Diagnostic, SyntaxError}, tags::{Inner, Node, Tag}, values::Value};
+
+    use super::{normalize, Graph, ImportError};
+
+    /// A fresh directory holding the given files
+    fn files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bftml-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for (path, text) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(Path::new("a/c.bftml"), normalize(Path::new("a/./b/../c.bftml")));
+        assert_eq!(Path::new("../c.bftml"), normalize(Path::new("a/../../c.bftml")));
+    }
+
+    #[test]
+    fn test_stitch() {
+        let dir = files("stitch", &[
+            ("app.bftml", "<!doctype bftml version=1>\n<column>\n    <import path=\"./parts/buttons.bftml\" />\n    <text>Count</text>\n</column>"),
+            ("parts/buttons.bftml", "<button label=\"+\" />\n<import path=\"../icon.bftml\" />\n<button label=\"-\" />"),
+            ("icon.bftml", "<icon />"),
+        ]);
+        let (graph, errors) = Graph::load(dir.join("app.bftml"));
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(3, graph.modules.len());
+        let stitched = graph.stitch();
+        assert!(stitched.document.prolog.is_some());
+
+        let button = |label: &str| {
+            let mut button = Tag::new("button");
+            button.with("label", Value::String(label.into()));
+            button
+        };
+        let mut text = Tag::new("text");
+        text.content("Count");
+        let mut column = Tag::new("column");
+        column.children(vec![button("+"), Tag::new("icon"), button("-"), text]);
+        assert_eq!(vec![Node::Tag(column)], stitched.document.roots);
+
+        // Spans point into the file each node came from
+        assert_eq!(dir.join("app.bftml"), stitched.file(&[0]));
+        assert_eq!(dir.join("parts/buttons.bftml"), stitched.file(&[0, 0]));
+        assert_eq!(dir.join("icon.bftml"), stitched.file(&[0, 1]));
+        assert_eq!(dir.join("parts/buttons.bftml"), stitched.file(&[0, 2]));
+        assert_eq!(dir.join("app.bftml"), stitched.file(&[0, 3]));
+        let Node::Tag(column) = &stitched.document.roots[0] else { panic!("Expected a tag") };
+        let Inner::Children(children) = &column.inner else { panic!("Expected children") };
+        assert_eq!(3, children[2].span().start.line);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let dir = files("errors", &[
+            ("a.bftml", "<column>\n    <import path=\"b.bftml\" />\n    <import path=\"missing.bftml\" />\n    <import />\n</column>"),
+            ("b.bftml", "<row>\n    <button label=%% />\n    <icon />\n</row>\n<text"),
+        ]);
+        let (graph, errors) = Graph::load(dir.join("a.bftml"));
+        let [
+            ImportError::BadImport { file: bad, .. },
+            ImportError::Syntax { file: first, error: SyntaxError::BadValue { .. } },
+            ImportError::Syntax { file: second, error: SyntaxError::Expected { .. } },
+            ImportError::Io { import: Some((missing, _)), .. },
+        ] = &errors[..] else {
+            panic!("{errors:?}");
+        };
+        assert_eq!(&dir.join("a.bftml"), bad);
+        // Every syntax error in a file is found, and the rest of it is still stitched in
+        assert_eq!(&dir.join("b.bftml"), first);
+        assert_eq!(&dir.join("b.bftml"), second);
+        assert_eq!(2, errors[1].labels()[0].span.start.line);
+        assert_eq!(&dir.join("a.bftml"), missing);
+        let Node::Tag(column) = &graph.stitch().document.roots[0] else { panic!("Expected a tag") };
+        let Inner::Children(children) = &column.inner else { panic!("Expected children") };
+        assert!(matches!(&children[..], [Node::Tag(row), Node::Error(_)] if row.name == "row"), "{children:?}");
+        // Reports name the file and line of the `<import>`
+        let report = errors[3].report(graph.text(errors[3].file().unwrap()).unwrap()).with_path("a.bftml").to_string();
+        assert!(report.contains("--> a.bftml:3:5"), "{report}");
+        fs::remove_dir_all(dir).unwrap();
+
+        // Markup cut off before the roots or after them is reported like any other, rather than aborting the load
+        let dir = files("cut", &[
+            ("a.bftml", "<column>\n    <import path=\"b.bftml\" />\n    <import path=\"c.bftml\" />\n</column>"),
+            ("b.bftml", "<row />\n<!-- unfinished"),
+            ("c.bftml", "<!doctype bftml\n<icon />"),
+        ]);
+        let (graph, errors) = Graph::load(dir.join("a.bftml"));
+        let [ImportError::Syntax { file: comment, .. }, ImportError::Syntax { file: doctype, .. }] = &errors[..] else { panic!("{errors:?}") };
+        assert_eq!(&dir.join("b.bftml"), comment);
+        assert_eq!(&dir.join("c.bftml"), doctype);
+        let report = errors[0].report(graph.text(comment).unwrap()).to_string();
+        assert!(report.contains("`-->`"), "{report}");
+        let Node::Tag(column) = &graph.stitch().document.roots[0] else { panic!("Expected a tag") };
+        let Inner::Children(children) = &column.inner else { panic!("Expected children") };
+        assert!(matches!(&children[..], [Node::Tag(row), Node::Error(_), Node::Error(_), Node::Tag(icon)] if row.name == "row" && icon.name == "icon"), "{children:?}");
+        fs::remove_dir_all(dir).unwrap();
+
+        let dir = files("cycle", &[
+            ("a.bftml", "<column>\n    <import path=\"b.bftml\" />\n</column>"),
+            ("b.bftml", "<row>\n    <import path=\"./a.bftml\" />\n</row>"),
+        ]);
+        let (graph, errors) = Graph::load(dir.join("a.bftml"));
+        let [ImportError::Cycle { file, span, chain }] = &errors[..] else { panic!("{errors:?}") };
+        assert_eq!(&dir.join("b.bftml"), file);
+        assert_eq!(2, span.start.line);
+        assert_eq!(&vec![dir.join("a.bftml"), dir.join("b.bftml"), dir.join("a.bftml")], chain);
+        // The cycle is cut where it closes
+        assert_eq!(1, graph.stitch().document.roots.len());
+        assert!(matches!(Graph::load(dir.join("c.bftml")).1[..], [ImportError::Io { import: None, .. }]));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
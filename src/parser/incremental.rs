@@ -0,0 +1,455 @@
+//! Parsing a tree again after an edit to its text, for hot reload and editors.
+//! Only the innermost tag around the edit is parsed again. Every other tag is moved over
+//! from the previous tree as it is, with its spans shifted to match the new text.
+
+use std::{fmt, ops::Range};
+
+use indexmap::{map::MutableKeys, set::MutableValues};
+use winnow::stream::Stream;
+
+use super::{document::{document, end, Document}, error::{self, SyntaxError}, expr::Expr, span::{Position, Source, Span, Spanned}, tags::{single, Inner, Node, Tag}, values::Value};
+
+/// Replaces the bytes in `range` with `text`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Edit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+impl Edit {
+    pub fn new(range: Range<usize>, text: &str) -> Self {
+        Edit { range, text: text.into() }
+    }
+
+    /// The text after the edit, unless the range is out of bounds or does not lie on character boundaries
+    pub fn apply(&self, text: &str) -> Result<String, ReparseError> {
+        if text.get(self.range.clone()).is_none() {
+            return Err(ReparseError::BadEdit { range: self.range.clone(), len: text.len() });
+        }
+        let mut edited = text.to_string();
+        edited.replace_range(self.range.clone(), &self.text);
+        Ok(edited)
+    }
+
+    /// How many bytes longer the text gets
+    fn delta(&self) -> isize {
+        self.text.len() as isize - self.range.len() as isize
+    }
+}
+
+/// The way from the root tag down to a node, as the index of each child along the way.
+/// The root itself is the empty path.
+pub type Path = Vec<usize>;
+
+#[derive(Clone, Debug)]
+pub struct Reparsed {
+    pub tag: Tag,
+    /// The text after the edit, which the spans in `tag` point into
+    pub text: String,
+    /// The nodes that differ from the previous tree, in order. Nodes within a changed node are not listed
+    /// on their own, and neither are the tags around it or nodes that only moved.
+    pub changed: Vec<Path>,
+}
+
+/// As [`Reparsed`], for a whole document
+#[derive(Clone, Debug)]
+pub struct ReparsedDocument {
+    pub document: Document,
+    pub text: String,
+    /// Paths start with the index of the root. The empty path means the prolog or the roots themselves changed.
+    pub changed: Vec<Path>,
+}
+
+/// Why a tree could not be parsed again after an edit
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReparseError {
+    /// The range of the edit is out of bounds of the text, `len` bytes long, or does not lie on character boundaries
+    BadEdit { range: Range<usize>, len: usize },
+    Syntax(SyntaxError),
+}
+
+impl fmt::Display for ReparseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReparseError::BadEdit { range, len } => {
+                write!(f, "cannot edit bytes {}..{} of a text {len} bytes long", range.start, range.end)
+            }
+            ReparseError::Syntax(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for ReparseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReparseError::BadEdit { .. } => None,
+            ReparseError::Syntax(error) => Some(error),
+        }
+    }
+}
+
+impl From<SyntaxError> for ReparseError {
+    fn from(error: SyntaxError) -> Self {
+        ReparseError::Syntax(error)
+    }
+}
+
+/// Applies `edit` to the text of `source`, which `previous` was parsed from, and parses only the innermost tag around it.
+/// When the edit changes where that tag ends, the tags around it are tried in turn, up to the whole text.
+pub fn reparse(previous: Tag, source: &Source, edit: &Edit) -> Result<Reparsed, ReparseError> {
+    let text = edit.apply(source.text)?;
+    let mut edited = Source::new(&text);
+    edited.keep_comments = source.keep_comments;
+
+    let path = around(children(&previous), &edit.range);
+    let mut tree = previous;
+    Shift { from: edit.range.end, delta: edit.delta(), source: &edited }.tag(&mut tree);
+    if let Some(changed) = narrow(&mut tree, &path, 1, &[], &edited) {
+        return Ok(Reparsed { tag: tree, text, changed });
+    }
+
+    let mut input = edited.input();
+    let tag = single(&mut input).and_then(|tag| end(&mut input).map(|()| tag)).map_err(error::finish)?;
+    let mut changed = Vec::new();
+    diff(&tree, &tag, &mut Vec::new(), &mut changed);
+    Ok(Reparsed { tag, text, changed })
+}
+
+/// As [`reparse`], for a whole document. Only an edit within a root tag is kept to it, anything else parses the document again.
+pub fn reparse_document(previous: Document, source: &Source, edit: &Edit) -> Result<ReparsedDocument, ReparseError> {
+    let text = edit.apply(source.text)?;
+    let mut edited = Source::new(&text);
+    edited.keep_comments = source.keep_comments;
+
+    let path = around(&previous.roots, &edit.range);
+    let mut tree = previous;
+    Shift { from: edit.range.end, delta: edit.delta(), source: &edited }.document(&mut tree);
+    if let Some((&i, path)) = path.split_first() {
+        let Node::Tag(root) = &mut tree.roots[i] else { unreachable!("Paths only lead through tags") };
+        if let Some(changed) = narrow(root, path, 0, &[i], &edited) {
+            return Ok(ReparsedDocument { document: tree, text, changed });
+        }
+    }
+
+    let document = document(&mut edited.input()).map_err(error::finish)?;
+    let mut changed = Vec::new();
+    if tree.prolog == document.prolog && tree.roots.len() == document.roots.len() {
+        diff_nodes(&tree.roots, &document.roots, &mut Vec::new(), &mut changed);
+    } else {
+        changed.push(Vec::new());
+    }
+    Ok(ReparsedDocument { document, text, changed })
+}
+
+/// Parses the innermost tag along `path` within `root` again, then each tag around it in turn until one ends
+/// where it did before, going no higher than `min` tags deep. Gives the paths that changed, behind `prefix`.
+fn narrow(root: &mut Tag, path: &[usize], min: usize, prefix: &[usize], edited: &Source) -> Option<Vec<Path>> {
+    for depth in (min..=path.len()).rev() {
+        let slot = at(root, &path[..depth]);
+        let mut input = edited.input();
+        let _ = input.next_slice(slot.span.start.offset);
+        // Anything after the tag is unchanged, so it parses the same as long as the tag ends in the same place
+        let Ok(tag) = single(&mut input) else { continue };
+        if tag.span.end != slot.span.end {
+            continue;
+        }
+        let old = std::mem::replace(slot, tag);
+        let mut changed = Vec::new();
+        diff(&old, slot, &mut [prefix, &path[..depth]].concat(), &mut changed);
+        return Some(changed);
+    }
+    None
+}
+
+/// The path through `nodes` to the innermost tag holding `range` without touching its first or last byte,
+/// as an edit there could change where the tag starts or stops
+fn around(nodes: &[Node], range: &Range<usize>) -> Path {
+    let mut path = Vec::new();
+    let mut nodes = nodes;
+    while let Some((i, child)) = tags(nodes).find(|(_, child)| child.span.start.offset < range.start && range.end < child.span.end.offset) {
+        path.push(i);
+        nodes = children(child);
+    }
+    path
+}
+
+fn children(tag: &Tag) -> &[Node] {
+    match &tag.inner {
+        Inner::Children(children) => children,
+        _ => &[],
+    }
+}
+
+fn tags(nodes: &[Node]) -> impl Iterator<Item = (usize, &Tag)> {
+    nodes.iter().enumerate().filter_map(|(i, node)| match node {
+        Node::Tag(tag) => Some((i, tag)),
+        _ => None,
+    })
+}
+
+fn at<'t>(root: &'t mut Tag, path: &[usize]) -> &'t mut Tag {
+    path.iter().fold(root, |tag, &i| match &mut tag.inner {
+        Inner::Children(children) => match &mut children[i] {
+            Node::Tag(tag) => tag,
+            _ => unreachable!("Paths only lead through tags"),
+        },
+        _ => unreachable!("Paths only lead through tags"),
+    })
+}
+
+/// Compares children pairwise as long as the tags around them match, so a change is pinned to the innermost node it can be
+fn diff(old: &Tag, new: &Tag, path: &mut Path, changed: &mut Vec<Path>) {
+    if old == new {
+        return;
+    }
+    let same = old.name == new.name && old.traits == new.traits && old.attributes == new.attributes && old.comments == new.comments;
+    match (&old.inner, &new.inner) {
+        (Inner::Children(old), Inner::Children(new)) if same && old.len() == new.len() => diff_nodes(old, new, path, changed),
+        _ => changed.push(path.clone()),
+    }
+}
+
+fn diff_nodes(old: &[Node], new: &[Node], path: &mut Path, changed: &mut Vec<Path>) {
+    for (i, pair) in old.iter().zip(new).enumerate() {
+        path.push(i);
+        match pair {
+            (Node::Tag(old), Node::Tag(new)) => diff(old, new, path, changed),
+            (old, new) if old != new => changed.push(path.clone()),
+            _ => {}
+        }
+        path.pop();
+    }
+}
+
+/// Moves every position at or after `from` along by `delta` bytes, looking its line and column up again in `source`
+struct Shift<'a> {
+    from: usize,
+    delta: isize,
+    source: &'a Source<'a>,
+}
+
+impl Shift<'_> {
+    fn span(&self, span: &mut Span) {
+        self.position(&mut span.start);
+        self.position(&mut span.end);
+    }
+
+    fn position(&self, position: &mut Position) {
+        if position.offset >= self.from {
+            *position = self.source.position(position.offset.saturating_add_signed(self.delta));
+        }
+    }
+
+    fn document(&self, document: &mut Document) {
+        if let Some(prolog) = &mut document.prolog {
+            self.span(&mut prolog.doctype.span);
+            for (key, value) in prolog.attributes.iter_mut2() {
+                self.span(&mut key.span);
+                self.value(value);
+            }
+        }
+        for node in &mut document.roots {
+            self.node(node);
+        }
+    }
+
+    fn node(&self, node: &mut Node) {
+        match node {
+            Node::Tag(tag) => self.tag(tag),
+            Node::Comment(text) | Node::Error(text) => self.span(&mut text.span),
+        }
+    }
+
+    fn tag(&self, tag: &mut Tag) {
+        // Nothing within a tag that ends before the edit moves
+        if tag.span.end.offset < self.from {
+            return;
+        }
+        self.span(&mut tag.span);
+        self.span(&mut tag.name.span);
+        for i in 0..tag.traits.len() {
+            if let Some(name) = tag.traits.get_index_mut2(i) {
+                self.span(&mut name.span);
+            }
+        }
+        for (key, value) in tag.attributes.iter_mut2() {
+            self.span(&mut key.span);
+            self.value(value);
+        }
+        for (_, comment) in &mut tag.comments {
+            self.span(&mut comment.span);
+        }
+        match &mut tag.inner {
+            Inner::None => {}
+            Inner::Content(text) => self.span(&mut text.span),
+            Inner::Children(children) => children.iter_mut().for_each(|child| self.node(child)),
+        }
+    }
+
+    fn value(&self, value: &mut Spanned<Value>) {
+        self.span(&mut value.span);
+        self.inner(&mut value.value);
+    }
+
+    fn inner(&self, value: &mut Value) {
+        match value {
+            Value::List(items) => items.iter_mut().for_each(|item| self.value(item)),
+            Value::Map(entries) => {
+                for (key, value) in entries.iter_mut2() {
+                    self.span(&mut key.span);
+                    self.value(value);
+                }
+            }
+            Value::Expr(expr) => self.expr(expr),
+            _ => {}
+        }
+    }
+
+    fn expr(&self, expr: &mut Expr) {
+        match expr {
+            Expr::Ref(path) => path.iter_mut().for_each(|name| self.span(&mut name.span)),
+            Expr::Call { function, args } => {
+                function.iter_mut().for_each(|name| self.span(&mut name.span));
+                for arg in args {
+                    self.span(&mut arg.span);
+                    self.expr(&mut arg.value);
+                }
+            }
+            Expr::Literal(value) => self.inner(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parser::{document::Document, span::{Source, Span}, tags::{Inner, Node, Tag}, values::Value};
+
+    use super::{reparse, reparse_document, Edit, ReparseError};
+
+    const TEXT: &str = "\
+<column gap=4lp>
+    <row>
+        <text color=red>One</text>
+        <button onclick=select(1) label=\"one\" />
+    </row>
+    <row>
+        <text>Two</text>
+    </row>
+</column>";
+
+    /// Every span in the tree, as `==` leaves them out
+    fn spans(tag: &Tag, out: &mut Vec<Span>) {
+        out.extend([tag.span, tag.name.span]);
+        out.extend(tag.attributes.iter().flat_map(|(key, value)| [key.span, value.span]));
+        match &tag.inner {
+            Inner::Content(text) => out.push(text.span),
+            Inner::Children(children) => children.iter().for_each(|child| match child {
+                Node::Tag(tag) => spans(tag, out),
+                other => out.push(other.span()),
+            }),
+            Inner::None => {}
+        }
+    }
+
+    /// Checks the reparsed tree against parsing the edited text from scratch, spans and all
+    fn check(text: &str, edit: &Edit) -> super::Reparsed {
+        let source = Source::new(text);
+        let reparsed = reparse(text.parse().unwrap(), &source, edit).unwrap();
+        let expected: Tag = reparsed.text.parse().unwrap();
+        assert_eq!(expected, reparsed.tag);
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        spans(&expected, &mut a);
+        spans(&reparsed.tag, &mut b);
+        assert_eq!(a, b);
+        reparsed
+    }
+
+    #[test]
+    fn test_reparse() {
+        let at = TEXT.find("\"one\"").unwrap();
+        let edit = Edit::new(at..at + 5, "\"first\"\n");
+        let previous: Tag = TEXT.parse().unwrap();
+        let Inner::Children(rows) = &previous.inner else { panic!("Expected rows") };
+        let Node::Tag(last) = &rows[1] else { panic!("Expected a row") };
+        let name = last.name.as_ptr();
+
+        let reparsed = reparse(previous, &Source::new(TEXT), &edit).unwrap();
+        assert_eq!(vec![vec![0, 1]], reparsed.changed);
+        let Inner::Children(rows) = &reparsed.tag.inner else { panic!("Expected rows") };
+        let Node::Tag(last) = &rows[1] else { panic!("Expected a row") };
+        // Moved over rather than parsed again
+        assert_eq!(name, last.name.as_ptr());
+
+        let reparsed = check(TEXT, &edit);
+        let Inner::Children(rows) = &reparsed.tag.inner else { panic!("Expected rows") };
+        let Node::Tag(row) = &rows[0] else { panic!("Expected a row") };
+        let Inner::Children(items) = &row.inner else { panic!("Expected children") };
+        let Node::Tag(button) = &items[1] else { panic!("Expected a button") };
+        assert_eq!(Some(&Value::String("first".into())), button.attributes.get("label").map(|v| &v.value));
+    }
+
+    #[test]
+    fn test_reparse_structure() {
+        // Whitespace between children changes nothing
+        let at = TEXT.find("    <row>").unwrap();
+        assert!(check(TEXT, &Edit::new(at..at + 4, "")).changed.is_empty());
+
+        // Splitting a row in two changes where it ends, so the column is parsed again
+        let at = TEXT.find("<button").unwrap();
+        assert_eq!(vec![Vec::<usize>::new()], check(TEXT, &Edit::new(at..at, "</row><row>")).changed);
+
+        // Inserting right before a tag touches its first byte, so its parent is parsed again
+        let at = TEXT.find("<text>").unwrap();
+        assert_eq!(vec![vec![1]], check(TEXT, &Edit::new(at..at, "<spacer />")).changed);
+
+        let at = TEXT.find("One").unwrap();
+        assert_eq!(vec![vec![0, 0]], check(TEXT, &Edit::new(at..at + 3, "Uno")).changed);
+
+        let source = Source::new(TEXT);
+        let at = TEXT.find("</row>").unwrap();
+        assert!(matches!(reparse(TEXT.parse().unwrap(), &source, &Edit::new(at..at + 6, "")), Err(ReparseError::Syntax(_))));
+    }
+
+    #[test]
+    fn test_bad_edit() {
+        let source = Source::new("<text>héllo</text>");
+        for range in [17..20, 8..9, 0..30] {
+            let error = reparse(source.text.parse().unwrap(), &source, &Edit::new(range.clone(), "")).unwrap_err();
+            assert_eq!(ReparseError::BadEdit { range, len: 19 }, error);
+        }
+        assert_eq!("<text>hello</text>", Edit::new(7..9, "e").apply(source.text).unwrap());
+    }
+
+    #[test]
+    fn test_reparse_document() {
+        let text = format!("<!doctype bftml version=1>\n<!-- first -->\n<spacer />\n{TEXT}\n");
+        let source = Source::new(&text).keeping_comments();
+        let check = |edit: &Edit| {
+            let previous = crate::parser::document::document(&mut source.input()).unwrap();
+            let reparsed = reparse_document(previous, &source, edit).unwrap();
+            let edited = Source::new(&reparsed.text).keeping_comments();
+            let expected = crate::parser::document::document(&mut edited.input()).unwrap();
+            assert_eq!(expected, reparsed.document);
+            let (mut a, mut b) = (Vec::new(), Vec::new());
+            expected.tags().for_each(|tag| spans(tag, &mut a));
+            reparsed.document.tags().for_each(|tag| spans(tag, &mut b));
+            assert_eq!(a, b);
+            reparsed.changed
+        };
+
+        // Within a root, paths start with its index
+        let at = text.find("One").unwrap();
+        assert_eq!(vec![vec![2, 0, 0]], check(&Edit::new(at..at + 3, "Uno")));
+        let at = text.find("gap=4lp").unwrap();
+        assert_eq!(vec![vec![2]], check(&Edit::new(at..at + 7, "gap=8lp")));
+        let at = text.find("<spacer").unwrap();
+        assert_eq!(vec![vec![1]], check(&Edit::new(at + 1..at + 7, "divider")));
+        // Between the roots or in the prolog, the document is parsed again
+        assert_eq!(vec![Vec::<usize>::new()], check(&Edit::new(at..at, "<spacer />")));
+        assert_eq!(vec![Vec::<usize>::new()], check(&Edit::new(24..25, "2")));
+
+        let at = text.find("</column>").unwrap();
+        let previous: Document = text.parse().unwrap();
+        assert!(matches!(reparse_document(previous, &source, &Edit::new(at..at + 2, "")), Err(ReparseError::Syntax(_))));
+    }
+}
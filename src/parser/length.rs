@@ -0,0 +1,114 @@
+use std::fmt;
+
+use winnow::{combinator::alt, Parser};
+
+use super::{error::{Result, SyntaxError}, span::{spanned, Input}, values::number};
+
+/// A size or position along one axis, as written in markup
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
+pub enum Length {
+    /// Physical pixels, which shrink as the scale factor grows
+    Px(f64),
+    /// Logical pixels, which the shaders scale to physical ones
+    Lp(f64),
+    /// Percent of the parent's size
+    Percent(f64),
+    /// A share of the space left over once every other sibling has been sized
+    Fr(f64),
+}
+
+impl Length {
+    /// Resolves a lone length to logical pixels, where a fraction has the whole parent to itself
+    pub fn resolve(&self, parent: f32, scale: f32) -> f32 {
+        resolve(&[*self], parent, scale)[0]
+    }
+}
+
+/// Writes whole amounts without a fraction, as in `4lp` rather than `4.0lp`
+impl fmt::Display for Length {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (amount, unit) = match *self {
+            Length::Px(px) => (px, "px"),
+            Length::Lp(lp) => (lp, "lp"),
+            Length::Percent(percent) => (percent, "%"),
+            Length::Fr(fr) => (fr, "fr"),
+        };
+        // Larger whole floats could be out of range once read back as integers
+        if amount.fract() == 0.0 && amount.abs() < 1e15 {
+            write!(f, "{}{unit}", amount as i64)
+        } else {
+            write!(f, "{amount:?}{unit}")
+        }
+    }
+}
+
+/// Resolves the lengths of siblings laid out along the same axis of a parent to logical pixels.
+/// Fractions share whatever the other lengths leave of the parent, in proportion to their size.
+pub fn resolve(lengths: &[Length], parent: f32, scale: f32) -> Vec<f32> {
+    let fixed = |length: &Length| match *length {
+        Length::Px(px) => Some(px as f32 / scale),
+        Length::Lp(lp) => Some(lp as f32),
+        Length::Percent(percent) => Some(percent as f32 / 100.0 * parent),
+        Length::Fr(_) => None,
+    };
+    let used: f32 = lengths.iter().filter_map(fixed).sum();
+    let fractions: f64 = lengths.iter().map(|l| if let Length::Fr(fr) = l { *fr } else { 0.0 }).sum();
+    let free = (parent - used).max(0.0);
+    lengths.iter().map(|length| match length {
+        Length::Fr(_) if fractions == 0.0 => 0.0,
+        Length::Fr(fr) => (fr / fractions) as f32 * free,
+        other => fixed(other).unwrap_or_else(|| unreachable!("Only fractions are not fixed")),
+    }).collect()
+}
+
+/// A number directly followed by `px`, `lp`, `%` or `fr`
+pub fn length(s: &mut Input) -> Result<Length> {
+    let amount = spanned(number).parse_next(s)?;
+    let unit = alt(("px", "lp", "%", "fr")).parse_next(s)?;
+    if unit == "fr" && *amount < 0.0 {
+        return Err(SyntaxError::Expected { expected: "a fraction of at least zero", span: amount.span }.cut());
+    }
+    Ok(match unit {
+        "px" => Length::Px(*amount),
+        "lp" => Length::Lp(*amount),
+        "%" => Length::Percent(*amount),
+        _ => Length::Fr(*amount),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use winnow::Parser;
+
+    use crate::parser::span::Source;
+
+    use super::{length, resolve, Length};
+
+    fn parse(input: &str) -> Option<Length> {
+        length.parse_next(&mut Source::new(input).input()).ok()
+    }
+
+    #[test]
+    fn test_length() {
+        assert_eq!(Some(Length::Px(12.0)), parse("12px"));
+        assert_eq!(Some(Length::Lp(-1.5)), parse("-1.5lp"));
+        assert_eq!(Some(Length::Percent(50.0)), parse("50%"));
+        assert_eq!(Some(Length::Fr(2.0)), parse("2fr"));
+        assert_eq!(None, parse("-1fr"));
+        assert_eq!(None, parse("12"));
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(10.0, Length::Px(20.0).resolve(100.0, 2.0));
+        assert_eq!(20.0, Length::Lp(20.0).resolve(100.0, 2.0));
+        assert_eq!(25.0, Length::Percent(25.0).resolve(100.0, 2.0));
+        assert_eq!(100.0, Length::Fr(3.0).resolve(100.0, 2.0));
+
+        let lengths = [Length::Lp(20.0), Length::Fr(1.0), Length::Percent(20.0), Length::Fr(3.0)];
+        assert_eq!(vec![20.0, 15.0, 20.0, 45.0], resolve(&lengths, 100.0, 1.0));
+        // Fractions get nothing rather than going negative when the parent is overfull
+        assert_eq!(vec![150.0, 0.0], resolve(&[Length::Lp(150.0), Length::Fr(1.0)], 100.0, 1.0));
+    }
+}
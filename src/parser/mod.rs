@@ -1,4 +1,17 @@
 pub mod attributes;
+pub mod borrowed;
+pub mod color;
+pub mod document;
+pub mod error;
+pub mod escape;
+pub mod expr;
+pub mod ident;
+pub mod imports;
+pub mod incremental;
+pub mod length;
+pub mod printer;
+pub mod recover;
+pub mod span;
 pub mod traits;
 pub mod tags;
 pub mod values;
@@ -7,11 +20,12 @@ pub mod values;
 mod test {
     use winnow::Parser;
 
-    use crate::parser::{tags::{single, Tag}, values::Value};
+    use crate::parser::{span::Source, tags::{single, Tag}, values::Value};
 
     #[test]
     fn test_altogether() {
-        let mut text = "<button default foo=\"bar\" answer=42 based=true pi=3.14 range=0..1><foo /></button>";
+        let source = Source::new("<button default foo=\"bar\" answer=42 based=true pi=3.14 range=0..1><foo /></button>");
+        let mut text = source.input();
         let parsed = single.parse_next(&mut text);
         let mut expected = Tag::new("button");
         expected.set("default")
@@ -19,7 +33,7 @@ mod test {
             .with("answer", Value::Int(42))
             .with("based", Value::Bool(true))
             .with("pi", Value::Float(3.14))
-            .with("range", Value::Range(0..1))
+            .with("range", Value::Range((0..1).into()))
             .children(vec![Tag::new("foo")]);
         println!("{text}");
         assert_eq!(expected, parsed.unwrap());
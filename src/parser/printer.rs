@@ -0,0 +1,235 @@
+//! Writes trees back out as markup. Whatever a [`Printer`] writes parses back to an equal tree,
+//! as long as the tree holds names the parser would accept and finite floats.
+
+use std::{fmt::{self, Write}, ops::Range};
+
+use super::{document::Document, escape, tags::{Inner, Node, Tag, PRESERVE}};
+
+#[derive(Clone, Debug)]
+pub struct Printer {
+    /// Written once per level of nesting
+    pub indent: String,
+    /// Opening tags longer than this put each preset and attribute on a line of its own
+    pub width: usize,
+    /// Whether tags with neither children nor content are written `<tag />` rather than `<tag></tag>`
+    pub self_close: bool,
+}
+
+impl Default for Printer {
+    fn default() -> Self {
+        Printer { indent: "    ".into(), width: 100, self_close: true }
+    }
+}
+
+impl Printer {
+    pub fn tag(&self, tag: &Tag) -> String {
+        let mut out = String::new();
+        self.write_tag(&mut out, tag, 0).unwrap_or_else(|_| unreachable!("Writing to a string never fails"));
+        out
+    }
+
+    pub fn document(&self, document: &Document) -> String {
+        let mut out = String::new();
+        self.write_document(&mut out, document).unwrap_or_else(|_| unreachable!("Writing to a string never fails"));
+        out
+    }
+
+    fn write_document(&self, out: &mut impl Write, document: &Document) -> fmt::Result {
+        let mut roots = &document.roots[..];
+        if let Some(prolog) = &document.prolog {
+            // Comments written before the prolog stay there, going by their spans
+            let leading = roots.iter()
+                .take_while(|node| matches!(node, Node::Comment(text) if text.span.end.offset < prolog.doctype.span.start.offset))
+                .count();
+            for node in &roots[..leading] {
+                self.write_node(out, node, 0)?;
+                writeln!(out)?;
+            }
+            roots = &roots[leading..];
+            write!(out, "<!doctype {}", prolog.doctype.value)?;
+            for (key, value) in &prolog.attributes {
+                write!(out, " {}={}", key.value, value.value)?;
+            }
+            writeln!(out, ">")?;
+        }
+        for node in roots {
+            self.write_node(out, node, 0)?;
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    fn write_node(&self, out: &mut impl Write, node: &Node, level: usize) -> fmt::Result {
+        match node {
+            Node::Tag(tag) => self.write_tag(out, tag, level),
+            Node::Comment(text) => write!(out, "{}<!--{}-->", self.indent.repeat(level), text.value),
+            // Kept as it was written, as it never parsed in the first place
+            Node::Error(text) => write!(out, "{}{}", self.indent.repeat(level), text.value),
+        }
+    }
+
+    fn write_tag(&self, out: &mut impl Write, tag: &Tag, level: usize) -> fmt::Result {
+        let indent = self.indent.repeat(level);
+        let items: Vec<String> = tag.traits.iter().map(|name| name.value.clone())
+            .chain(tag.attributes.iter().map(|(key, value)| format!("{}={}", key.value, value.value)))
+            .collect();
+        let empty = tag.inner.is_empty() && tag.comments.is_empty();
+        let end = if empty && self.self_close { " />" } else { ">" };
+
+        let line: String = items.iter().map(|item| format!(" {item}")).collect();
+        if indent.len() + 1 + tag.name.len() + line.len() + end.len() <= self.width || items.is_empty() {
+            write!(out, "{indent}<{}{line}{end}", tag.name.value)?;
+        } else {
+            write!(out, "{indent}<{}", tag.name.value)?;
+            for item in &items {
+                write!(out, "\n{indent}{}{item}", self.indent)?;
+            }
+            write!(out, "\n{indent}{}", end.trim_start())?;
+        }
+        if empty && self.self_close {
+            return Ok(());
+        }
+
+        match &tag.inner {
+            Inner::Children(children) if !children.is_empty() => {
+                for child in children {
+                    writeln!(out)?;
+                    self.write_node(out, child, level + 1)?;
+                }
+                write!(out, "\n{indent}")?;
+            }
+            Inner::Content(text) => {
+                let preserve = tag.traits.contains(PRESERVE);
+                // Comments go back where they were found in the text
+                let mut written = 0;
+                for (offset, comment) in &tag.comments {
+                    let offset = (*offset).clamp(written, text.len());
+                    write!(out, "{}<!--{}-->", content(text, written..offset, preserve), comment.value)?;
+                    written = offset;
+                }
+                write!(out, "{}", content(text, written..text.len(), preserve))?;
+            }
+            _ => {
+                for (_, comment) in &tag.comments {
+                    write!(out, "<!--{}-->", comment.value)?;
+                }
+            }
+        }
+        write!(out, "</{}>", tag.name.value)
+    }
+}
+
+/// Verbatim text with markup in it reads better as a raw block than full of entities
+fn content(text: &str, range: Range<usize>, preserve: bool) -> String {
+    let run = &text[range.clone()];
+    if preserve && run.contains(['<', '&']) && !run.contains("]]>") {
+        format!("<![CDATA[{run}]]>")
+    } else {
+        escape::collapsed(text, range, preserve)
+    }
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Printer::default().write_tag(f, self, 0)
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Printer::default().write_document(f, self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parser::{document::Document, span::Source, tags::Tag, values::Value};
+
+    use super::Printer;
+
+    #[test]
+    fn test_print() {
+        let mut tag = Tag::new("column");
+        let mut button = Tag::new("button");
+        button.set("default").with("label", Value::String("\"+\"".into()));
+        let mut text = Tag::new("text");
+        text.content("1 < 2 & 3");
+        tag.children(vec![button, text, Tag::new("spacer")]);
+
+        let expected = "\
+<column>
+    <button default label=\"\\\"+\\\"\" />
+    <text>1 &lt; 2 &amp; 3</text>
+    <spacer />
+</column>";
+        assert_eq!(expected, tag.to_string());
+        assert_eq!(tag, expected.parse().unwrap());
+    }
+
+    #[test]
+    fn test_configured() {
+        let mut tag = Tag::new("box");
+        tag.set("rounded").with("color", Value::Int(1)).with("size", Value::Int(2));
+        tag.children(vec![Tag::new("a")]);
+        let printer = Printer { indent: "\t".into(), width: 20, self_close: false };
+        let expected = "<box\n\trounded\n\tcolor=1\n\tsize=2\n>\n\t<a></a>\n</box>";
+        assert_eq!(expected, printer.tag(&tag));
+        assert_eq!(tag, expected.parse().unwrap());
+    }
+
+    #[test]
+    fn test_whitespace() {
+        let mut text = Tag::new("text");
+        text.content(" a\n b  c ");
+        assert_eq!("<text>&#x20;a&#xA;&#x20;b&#x20;&#x20;c&#x20;</text>", text.to_string());
+        assert_eq!(text, text.to_string().parse().unwrap());
+
+        let mut code = Tag::new("code");
+        code.set("preserve").content("\n  if a < b {}\n");
+        assert_eq!("<code preserve><![CDATA[\n  if a < b {}\n]]></code>", code.to_string());
+        assert_eq!(code, code.to_string().parse().unwrap());
+        code.content("]]>");
+        assert_eq!("<code preserve>]]&gt;</code>", code.to_string());
+        assert_eq!(code, code.to_string().parse().unwrap());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let text = "\
+<!-- counter -->
+<!doctype bftml version=1>
+<column gap=8lp align=[x: 50%, y: 0.0..=1.0]>
+  <!-- buttons -->
+  <button onclick=increment() label=\"+\" color=hsl(120, 100%, 25%)/>
+  <text>Count: <!-- the value -->{counter}</text>
+  <pre preserve>  keep <!-- this -->  <![CDATA[<as is>]]>
+  </pre>
+  <ui:spacer-box weight=1fr>
+    <rect radius=[4, 8, 4, 8] fill=\"a\\u{1}b\" />
+  </ui:spacer-box>
+</column>
+<empty></empty>
+";
+        for source in [Source::new(text), Source::new(text).keeping_comments()] {
+            let document = crate::parser::document::document(&mut source.input()).unwrap();
+            for printer in [Printer::default(), Printer { indent: " ".into(), width: 0, self_close: false }] {
+                let printed = printer.document(&document);
+                let source = Source::new(&printed).keeping_comments();
+                let reparsed = crate::parser::document::document(&mut source.input()).unwrap();
+                assert_eq!(document, reparsed, "{printed}");
+                // Printing is idempotent, with comments kept on their side of the prolog
+                assert_eq!(printed, printer.document(&reparsed));
+            }
+        }
+        let source = Source::new(text).keeping_comments();
+        let printed = Printer::default().document(&crate::parser::document::document(&mut source.input()).unwrap());
+        assert!(printed.starts_with("<!-- counter -->\n<!doctype bftml version=1>\n<column"), "{printed}");
+
+        let source = Source::new("<text>Count: <!-- the value -->{counter}<!-- end --></text>").keeping_comments();
+        let tag = crate::parser::tags::single(&mut source.input()).unwrap();
+        assert_eq!(source.text, tag.to_string());
+
+        let document = Document::from_path(concat!(env!("CARGO_MANIFEST_DIR"), "/file.xml")).unwrap();
+        assert_eq!(document, document.to_string().parse().unwrap());
+    }
+}
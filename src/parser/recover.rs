@@ -0,0 +1,204 @@
+use std::cell::RefCell;
+
+use winnow::{ascii::multispace0, error::ErrMode, stream::Stream, token::{rest, take}, Parser};
+
+use super::{document::{document, Document}, error::{self, Result, SyntaxError}, ident, span::{spanned, Input, Source}, tags::{single, Node}};
+
+/// What the parser keeps track of to carry on past syntax errors
+#[derive(Debug, Default)]
+pub struct Recovery {
+    errors: RefCell<Vec<SyntaxError>>,
+    /// Names of the tags enclosing the current position, outermost first
+    open: RefCell<Vec<String>>,
+}
+
+impl<'s> Source<'s> {
+    pub fn recovering(text: &'s str) -> Self {
+        let mut source = Source::new(text);
+        source.recovery = Some(Recovery::default());
+        source
+    }
+
+    /// Takes the errors recorded so far, in the order they were found
+    pub fn errors(&self) -> Vec<SyntaxError> {
+        self.recovery.as_ref().map(|r| r.errors.take()).unwrap_or_default()
+    }
+
+    pub(crate) fn record(&self, error: SyntaxError) {
+        if let Some(recovery) = &self.recovery {
+            recovery.errors.borrow_mut().push(error);
+        }
+    }
+
+    pub(crate) fn enter(&self, name: &str) {
+        if let Some(recovery) = &self.recovery {
+            recovery.open.borrow_mut().push(name.into());
+        }
+    }
+
+    pub(crate) fn leave(&self) {
+        if let Some(recovery) = &self.recovery {
+            recovery.open.borrow_mut().pop();
+        }
+    }
+}
+
+/// Parses a tag like [`Tag::from_str`](std::str::FromStr), but keeps going past syntax errors.
+/// Invalid markup is replaced by [`Node::Error`] in the tree, and every error is returned alongside it.
+pub fn parse(text: &str) -> (Node, Vec<SyntaxError>) {
+    let source = Source::recovering(text);
+    let mut input = source.input();
+    let node = node.parse_next(&mut input).unwrap_or_else(|e| {
+        // Only reachable when the text does not start with a tag at all
+        source.record(error::finish(e));
+        let text: Result<_> = spanned(rest).parse_next(&mut input);
+        let text = text.unwrap_or_else(|_| unreachable!("Taking the rest never fails"));
+        Node::Error(text.map(String::from))
+    });
+    (node, source.errors())
+}
+
+/// Parses a whole file like [`Document::from_str`](std::str::FromStr), but keeps going past syntax errors
+pub fn parse_document(text: &str) -> (Document, Vec<SyntaxError>) {
+    let source = Source::recovering(text);
+    let document = document.parse_next(&mut source.input()).unwrap_or_else(|e| {
+        // Every cut is recovered from, so this is only ever an error that was not cut
+        source.record(error::finish(e));
+        Document::default()
+    });
+    (document, source.errors())
+}
+
+/// Parses a child tag, or when recovering, records why it is invalid and stands an error node in for it
+pub fn node(s: &mut Input) -> Result<Node> {
+    let source = s.state;
+    let Some(recovery) = &source.recovery else {
+        return single.map(Node::Tag).parse_next(s);
+    };
+    let start = s.checkpoint();
+    let depth = recovery.open.borrow().len();
+    match single.parse_next(s) {
+        Err(ErrMode::Cut(error)) => {
+            recovery.open.borrow_mut().truncate(depth);
+            recovery.errors.borrow_mut().push(error);
+            s.reset(&start);
+            let _ = multispace0.parse_next(s)?;
+            let length = extent(s, &recovery.open.borrow());
+            let skipped = spanned(take(length)).parse_next(s)?;
+            Ok(Node::Error(skipped.map(String::from)))
+        }
+        result => result.map(Node::Tag),
+    }
+}
+
+/// How far a broken tag reaches, found by balancing opening and closing tags without trusting anything else.
+/// A closing tag for one of the `ancestors` ends it early, so their own closing tags still line up.
+fn extent(text: &str, ancestors: &[String]) -> usize {
+    let name = |s: &str| s.split(|c: char| !ident::is_continue(c) && c != ':').next().unwrap_or("").to_string();
+    let mut open: Vec<String> = Vec::new();
+    let mut offset = 0;
+    while let Some(found) = text[offset..].find('<') {
+        let at = offset + found;
+        if text[at..].starts_with("<!--") {
+            offset = text[at..].find("-->").map_or(text.len(), |i| at + i + 3);
+            continue;
+        }
+        if text[at..].starts_with("<![CDATA[") {
+            offset = text[at..].find("]]>").map_or(text.len(), |i| at + i + 3);
+            continue;
+        }
+        // A tag missing its `>` ends where the next one starts, and cannot be balanced
+        let next = text[at + 1..].find('<').map_or(text.len(), |i| at + 1 + i);
+        let end = text[at..next].find('>').map_or(next, |i| at + i + 1);
+        if let Some(close) = text[at..].strip_prefix("</") {
+            let name = name(close);
+            if let Some(depth) = open.iter().rposition(|n| *n == name) {
+                open.truncate(depth);
+            } else if ancestors.contains(&name) {
+                return at;
+            }
+        } else if text[..end].ends_with('>') && !text[..end].ends_with("/>") {
+            let name = name(&text[at + 1..]);
+            if !name.is_empty() {
+                open.push(name);
+            }
+        }
+        offset = end;
+        if open.is_empty() {
+            return end;
+        }
+    }
+    text.len()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parser::{error::SyntaxError, tags::{Inner, Node, Tag}, values::Value};
+
+    use super::{extent, parse, parse_document};
+
+    #[test]
+    fn test_extent() {
+        assert_eq!(6, extent("<foo a<bar />", &[]));
+        assert_eq!(16, extent("<foo><bar></foo></baz>", &[]));
+        assert_eq!(5, extent("<foo></baz>", &["baz".into()]));
+        // Stray closing tags belong to the broken tag
+        assert_eq!(17, extent("<foo></bar></foo><baz/>", &["baz".into()]));
+        assert_eq!(26, extent("<foo><!-- </foo> --></foo>", &[]));
+    }
+
+    #[test]
+    fn test_recover() {
+        let (node, errors) = parse("<column>\n    <button label=%% />\n    <button label=\"-\"/>\n    <row></column>\n</column>");
+        assert_eq!(2, errors.len());
+        assert!(matches!(errors[0], SyntaxError::BadValue { .. }));
+        assert!(matches!(errors[1], SyntaxError::MismatchedClose { .. }));
+
+        let Node::Tag(column) = node else { panic!("Expected the column to survive") };
+        let mut button = Tag::new("button");
+        button.with("label", Value::String("-".into()));
+        let mut expected = Tag::new("column");
+        expected.inner = Inner::Children(vec![
+            Node::Error("<button label=%% />".into()),
+            Node::Tag(button),
+            Node::Error("<row>".into()),
+        ]);
+        assert_eq!(expected, column);
+    }
+
+    #[test]
+    fn test_recover_root() {
+        let (node, errors) = parse("<foo>");
+        assert!(matches!(errors[..], [SyntaxError::UnclosedTag { .. }]));
+        assert_eq!(Node::Error("<foo>".into()), node);
+
+        let (node, errors) = parse("bananas");
+        assert_eq!(1, errors.len());
+        assert_eq!(Node::Error("bananas".into()), node);
+    }
+
+    #[test]
+    fn test_recover_comments() {
+        let (document, errors) = parse_document("<!-- foo");
+        assert!(matches!(errors[..], [SyntaxError::Expected { .. }]), "{errors:?}");
+        assert_eq!(vec![Node::Error("<!-- foo".into())], document.roots);
+
+        // Cut off at the end of the file, after the roots
+        let (document, errors) = parse_document("<foo/>\n<!-- x");
+        assert_eq!(1, errors.len());
+        assert_eq!(vec![Node::Tag(Tag::new("foo")), Node::Error("<!-- x".into())], document.roots);
+        assert_eq!(7..13, document.roots[1].span().bytes());
+    }
+
+    #[test]
+    fn test_recover_prolog() {
+        let (document, errors) = parse_document("<!doctype bftml\n<foo/>");
+        assert!(matches!(errors[..], [SyntaxError::Expected { expected: "`>`", .. }]), "{errors:?}");
+        assert_eq!(None, document.prolog);
+        assert_eq!(vec![Node::Error("<!doctype bftml\n".into()), Node::Tag(Tag::new("foo"))], document.roots);
+
+        let (document, errors) = parse_document("<!doctype 1>\n<foo/>");
+        assert_eq!(1, errors.len());
+        assert_eq!(Some(&Node::Tag(Tag::new("foo"))), document.roots.last());
+    }
+}
@@ -0,0 +1,222 @@
+use std::{borrow::Borrow, fmt, hash::{Hash, Hasher}, ops::{Deref, DerefMut, Range}};
+
+use winnow::{error::ParserError, stream::{Location, LocatingSlice, Stateful}, Parser};
+
+use super::recover::Recovery;
+
+/// The stream every parser in this module runs over.
+/// It tracks byte offsets, and carries the [`Source`] so they can be turned into lines and columns.
+pub type Input<'s> = Stateful<LocatingSlice<&'s str>, &'s Source<'s>>;
+
+/// A point in the source text, both as a byte offset and as a 1-based line and column
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Position {
+    pub offset: usize,
+    pub line: u32,
+    pub column: u32,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    pub fn bytes(&self) -> Range<usize> {
+        self.start.offset..self.end.offset
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.start.line, self.start.column)
+    }
+}
+
+/// The original text being parsed, with the line starts indexed up front
+/// so every span can be resolved to a line and column without rescanning.
+#[derive(Debug)]
+pub struct Source<'s> {
+    pub text: &'s str,
+    lines: Vec<usize>,
+    /// Only set when the parser should carry on past errors, see [`Source::recovering`]
+    pub(crate) recovery: Option<Recovery>,
+    pub(crate) keep_comments: bool,
+}
+
+impl<'s> Source<'s> {
+    pub fn new(text: &'s str) -> Self {
+        let lines = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Source { text, lines, recovery: None, keep_comments: false }
+    }
+
+    /// Keeps comments in the parsed tree instead of dropping them, so it can be printed back out
+    pub fn keeping_comments(mut self) -> Self {
+        self.keep_comments = true;
+        self
+    }
+
+    pub fn input(&'s self) -> Input<'s> {
+        Stateful { input: LocatingSlice::new(self.text), state: self }
+    }
+
+    pub fn position(&self, offset: usize) -> Position {
+        let line = self.lines.partition_point(|&start| start <= offset);
+        let start = self.lines[line - 1];
+        let column = self.text[start..offset].chars().count() + 1;
+        Position { offset, line: line as u32, column: column as u32 }
+    }
+
+    pub fn span(&self, bytes: Range<usize>) -> Span {
+        Span { start: self.position(bytes.start), end: self.position(bytes.end) }
+    }
+}
+
+/// A value along with where it came from.
+///
+/// Spans are metadata, so they are ignored when comparing and hashing.
+/// That way a tree built in code compares equal to the same tree parsed from text.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(value: T, span: Span) -> Self {
+        Spanned { value, span }
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Spanned<U> {
+        Spanned { value: f(self.value), span: self.span }
+    }
+}
+
+impl<T> From<T> for Spanned<T> {
+    fn from(value: T) -> Self {
+        Spanned { value, span: Span::default() }
+    }
+}
+
+impl From<&str> for Spanned<String> {
+    fn from(value: &str) -> Self {
+        Spanned::from(value.to_string())
+    }
+}
+
+impl<T> Deref for Spanned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for Spanned<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T: PartialEq> PartialEq for Spanned<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T: Eq> Eq for Spanned<T> {}
+
+impl PartialEq<str> for Spanned<String> {
+    fn eq(&self, other: &str) -> bool {
+        self.value == other
+    }
+}
+
+impl PartialEq<&str> for Spanned<String> {
+    fn eq(&self, other: &&str) -> bool {
+        self.value == *other
+    }
+}
+
+impl PartialEq<Spanned<String>> for str {
+    fn eq(&self, other: &Spanned<String>) -> bool {
+        *self == other.value
+    }
+}
+
+impl PartialEq<Spanned<String>> for &str {
+    fn eq(&self, other: &Spanned<String>) -> bool {
+        *self == other.value
+    }
+}
+
+impl<T: Hash> Hash for Spanned<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.value.hash(state)
+    }
+}
+
+/// Written as the bare value, as spans only make sense against the text they came from
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for Spanned<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for Spanned<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Spanned::from)
+    }
+}
+
+/// Lets maps and sets keyed by spanned names be queried with a plain `&str`
+impl Borrow<str> for Spanned<String> {
+    fn borrow(&self) -> &str {
+        &self.value
+    }
+}
+
+pub fn spanned<'s, O, E, P>(mut parser: P) -> impl Parser<Input<'s>, Spanned<O>, E>
+where
+    P: Parser<Input<'s>, O, E>,
+    E: ParserError<Input<'s>>,
+{
+    move |s: &mut Input<'s>| {
+        let start = s.current_token_start();
+        let value = parser.parse_next(s)?;
+        let span = s.state.span(start..s.previous_token_end());
+        Ok(Spanned { value, span })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use winnow::{ascii::alphanumeric1, Result, Parser};
+
+    use super::{spanned, Position, Source};
+
+    #[test]
+    fn test_position() {
+        let source = Source::new("foo\nbär baz");
+        assert_eq!(Position { offset: 0, line: 1, column: 1 }, source.position(0));
+        assert_eq!(Position { offset: 4, line: 2, column: 1 }, source.position(4));
+        // Columns count characters, not bytes
+        assert_eq!(Position { offset: 9, line: 2, column: 5 }, source.position(9));
+    }
+
+    #[test]
+    fn test_spanned() {
+        let source = Source::new("foo bar");
+        let mut input = source.input();
+        let _: Result<&str> = "foo ".parse_next(&mut input);
+        let parsed: Result<_> = spanned(alphanumeric1).parse_next(&mut input);
+        let parsed = parsed.unwrap();
+        assert_eq!("bar", parsed.value);
+        assert_eq!(4..7, parsed.span.bytes());
+    }
+}
@@ -1,28 +1,100 @@
-use std::str::FromStr;
+use std::{borrow::Cow, str::FromStr};
 
 use indexmap::{IndexMap, IndexSet};
-use winnow::{ascii::{alphanumeric1, multispace0}, combinator::{alt, not, repeat}, error::{ContextError, ErrMode}, token::take_till, Result, Parser};
+use winnow::{ascii::multispace0, combinator::{alt, cut_err, opt, peek, preceded, repeat}, error::ErrMode, stream::Location, token::{take_till, take_until}, Parser};
 
-use super::{traits, attributes, values::Value};
+use super::{document::end, error::{self, Result, SyntaxError}, escape, ident::identifier, recover::node, span::{spanned, Input, Source, Span, Spanned}, traits, attributes, values::Value};
 
-#[derive(Clone, Debug, PartialEq)]
-enum Inner {
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
+pub enum Inner {
     None,
-    Content(String),
-    Children(Vec<Tag>)
+    Content(Spanned<String>),
+    Children(Vec<Node>)
+}
+
+impl Inner {
+    /// Whether there is neither content nor any children, however that was written
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Inner::None => true,
+            Inner::Content(text) => text.is_empty(),
+            Inner::Children(children) => children.is_empty(),
+        }
+    }
+}
+
+/// `<tag />` and `<tag></tag>` mean the same, so every empty inner is equal
+impl PartialEq for Inner {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Inner::Content(a), Inner::Content(b)) => a == b,
+            (Inner::Children(a), Inner::Children(b)) => a == b,
+            (a, b) => a.is_empty() && b.is_empty(),
+        }
+    }
 }
 
+// Tags far outnumber the other nodes, so they are not boxed
+#[allow(clippy::large_enum_variant)]
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
+pub enum Node {
+    Tag(Tag),
+    /// Only present when parsing with [`Source::keeping_comments`]
+    Comment(Spanned<String>),
+    /// Markup that failed to parse, kept verbatim so an error region can be drawn in its place
+    Error(Spanned<String>),
+}
+
+impl Node {
+    pub fn span(&self) -> Span {
+        match self {
+            Node::Tag(tag) => tag.span,
+            Node::Comment(text) | Node::Error(text) => text.span,
+        }
+    }
+}
+
+impl From<Tag> for Node {
+    fn from(tag: Tag) -> Self {
+        Node::Tag(tag)
+    }
+}
+
+/// Comments taken out of content, each with the byte offset into the text where it was found
+pub type Comments<T = String> = Vec<(usize, Spanned<T>)>;
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Tag {
-    pub name: String,
-    pub traits: IndexSet<String>,
-    pub attributes: IndexMap<String, Value>,
-    pub inner: Inner
+    pub name: Spanned<String>,
+    pub traits: IndexSet<Spanned<String>>,
+    pub attributes: IndexMap<Spanned<String>, Spanned<Value>>,
+    pub inner: Inner,
+    /// Comments from within the content, which are kept out of its text.
+    /// Like [`Node::Comment`], these are only kept when parsing with [`Source::keeping_comments`].
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Vec::is_empty"))]
+    pub comments: Comments,
+    /// Everything from the opening `<` to the final `>`
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub span: Span,
+}
+
+/// Spans are left out so that built and parsed trees compare equal
+impl PartialEq for Tag {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.traits == other.traits
+            && self.attributes == other.attributes
+            && self.inner == other.inner
+            && self.comments == other.comments
+    }
 }
 
 impl Tag {
     pub fn new(name: &str) -> Self {
-        Tag { name: name.into(), traits: IndexSet::new(), attributes: IndexMap::new(), inner: Inner::None }
+        Tag { name: name.into(), traits: IndexSet::new(), attributes: IndexMap::new(), inner: Inner::None, comments: Vec::new(), span: Span::default() }
     }
 
     pub fn set(&mut self, key: &str) -> &mut Self {
@@ -31,7 +103,7 @@ impl Tag {
     }
 
     pub fn with(&mut self, key: &str, value: Value) -> &mut Self {
-        self.attributes.insert(key.into(), value);
+        self.attributes.insert(key.into(), value.into());
         self
     }
 
@@ -41,58 +113,230 @@ impl Tag {
     }
 
     pub fn children(&mut self, tags: Vec<Tag>) -> &mut Self {
-        self.inner = Inner::Children(tags);
+        self.inner = Inner::Children(tags.into_iter().map(Node::Tag).collect());
         self
     }
 }
 
-pub fn single(s: &mut &str) -> Result<Tag> {
-    let _ = multispace0.parse_next(s)?;
-    let _ = "<".parse_next(s)?;
-    let name: String = alphanumeric1.parse_next(s)?.into();
+pub fn single(s: &mut Input) -> Result<Tag> {
+    let (start, name) = open.parse_next(s)?;
+    // Past the name this can only be a tag, so nothing below backtracks
 
     let traits = traits::many.parse_next(s)?;
 
     let attributes = attributes::many.parse_next(s)?;
+
+    let (body, span) = body(s, start, name, traits.contains(PRESERVE), many)?;
+    let (inner, comments) = match body {
+        Body::Empty => (Inner::None, Vec::new()),
+        Body::Children(children) => (Inner::Children(children), Vec::new()),
+        Body::Content(text, comments) => {
+            let comments = comments.into_iter().map(|(offset, comment)| (offset, comment.map(String::from))).collect();
+            (Inner::Content(text.map(Cow::into_owned)), comments)
+        }
+    };
+    Ok(Tag { name: name.map(String::from), traits, attributes, inner, comments, span })
+}
+
+/// What follows the attributes of a tag, with the children left to the tree being built
+pub(crate) enum Body<'s, C> {
+    /// Closed with `/>`
+    Empty,
+    Children(C),
+    Content(Spanned<Cow<'s, str>>, Comments<&'s str>),
+}
+
+/// Everything after the attributes of the tag `name`, which starts at `start`, up to and including its closing tag.
+/// Also gives the span of the whole tag.
+pub(crate) fn body<'s, C>(
+    s: &mut Input<'s>,
+    start: usize,
+    name: Spanned<&'s str>,
+    preserve: bool,
+    children: impl Parser<Input<'s>, C, ErrMode<SyntaxError>>,
+) -> Result<(Body<'s, C>, Span)> {
+    let _ = multispace0.parse_next(s)?;
+    if opt("/>").parse_next(s)?.is_some() {
+        return Ok((Body::Empty, s.state.span(start..s.previous_token_end())));
+    }
+
+    let _ = cut_err(">").context("`>` or `/>`").parse_next(s)?;
+    let opened = s.state.span(start..s.previous_token_end());
+
+    s.state.enter(name.value);
+    let body = alt((
+        children.map(Body::Children),
+        spanned(content(preserve)).map(|content| {
+            let (text, comments) = content.value;
+            Body::Content(Spanned::new(text, content.span), comments)
+        }),
+    )).parse_next(s)?;
+    s.state.leave();
+
+    close(s, name, opened, matches!(body, Body::Children(_)))?;
+    Ok((body, s.state.span(start..s.previous_token_end())))
+}
+
+/// The `<` and name that start a tag, along with the offset of the `<`
+pub(crate) fn open<'s>(s: &mut Input<'s>) -> Result<(usize, Spanned<&'s str>)> {
     let _ = multispace0.parse_next(s)?;
+    let start = s.current_token_start();
+    let _ = "<".parse_next(s)?;
+    let name = spanned(identifier).parse_next(s)?;
+    Ok((start, name))
+}
 
-    let out: Result<&str> = "/>".parse_next(s);
-    if out.is_ok() {
-        return Ok(Tag { name, traits, attributes, inner: Inner::None })
+/// The closing tag after the inner of the tag `name`, which was opened at `opened`
+fn close(s: &mut Input, name: Spanned<&str>, opened: Span, children: bool) -> Result<()> {
+    // Whitespace between children is insignificant, but belongs to content
+    if children {
+        let _ = multispace0.parse_next(s)?;
+    }
+    if s.is_empty() {
+        return Err(SyntaxError::UnclosedTag { name: name.value.into(), span: opened }.cut());
+    }
+    if opt("</").parse_next(s)?.is_none() {
+        let span = spanned(mixed).parse_next(s)?.span;
+        return Err(SyntaxError::MixedInner { name: name.value.into(), span }.cut());
     }
+    let close = cut_err(spanned(identifier)).context("closing tag name").parse_next(s)?;
+    if close.value != name.value {
+        return Err(SyntaxError::MismatchedClose { open: name.map(String::from), close: close.map(String::from) }.cut());
+    }
+    let _ = cut_err(">").context("`>`").parse_next(s)?;
+    Ok(())
+}
 
-    let _ = ">".parse_next(s)?;
+/// Children, with any comments between them. Comments alone do not count as children.
+fn many(s: &mut Input) -> Result<Vec<Node>> {
+    let keep = s.state.keep_comments;
+    repeat(1.., alt((node, preceded(multispace0, comment).map(|c| Node::Comment(c.map(String::from))))))
+        .fold(Vec::new, |mut acc: Vec<Node>, item| {
+            acc.push(item);
+            acc
+        })
+        .verify(|nodes: &Vec<Node>| nodes.iter().any(|node| !matches!(node, Node::Comment(_))))
+        .map(|mut nodes| {
+            nodes.retain(|node| keep || !matches!(node, Node::Comment(_)));
+            nodes
+        })
+        .parse_next(s)
+}
 
+/// The name of the preset that keeps the whitespace in content as it was written
+pub const PRESERVE: &str = "preserve";
+
+/// Text up to the next tag, along with the comments taken out of it if they are being kept.
+///
+/// Unless `preserve` is set, whitespace at either end is dropped and every other run of it becomes a single space.
+/// Whitespace written as an entity or within a raw block is always kept.
+/// The text is only copied when it has to be changed from what was written.
+fn content<'s>(preserve: bool) -> impl Parser<Input<'s>, (Cow<'s, str>, Comments<&'s str>), ErrMode<SyntaxError>> {
+    move |s: &mut Input<'s>| {
+        let keep = s.state.keep_comments;
+        repeat(0.., alt((
+            take_till(1.., ['<', '&']).map(Run::Text),
+            escape::entity.map(Run::Char),
+            raw.map(Run::Raw),
+            comment.map(Run::Comment),
+        ))).fold(|| (Text::default(), Vec::new()), move |(mut text, mut comments): (Text<'s>, Comments<&'s str>), run| {
+            match run {
+                Run::Text(run) if !preserve => text.collapse(run),
+                Run::Text(run) | Run::Raw(run) => text.verbatim(Cow::Borrowed(run)),
+                Run::Char(c) => text.verbatim(Cow::Owned(c.into())),
+                // A comment after whitespace goes after the space it collapses to
+                Run::Comment(comment) if keep => comments.push((text.value.len() + usize::from(text.space), comment)),
+                Run::Comment(_) => {}
+            }
+            (text, comments)
+        }).map(|(text, mut comments)| {
+            let end = text.value.len();
+            comments.iter_mut().for_each(|(offset, _)| *offset = (*offset).min(end));
+            (text.value, comments)
+        }).parse_next(s)
+    }
+}
 
-    let inner = alt((
-        many.map(|children| Inner::Children(children)),
-        content.map(|content| Inner::Content(content.into())),
-    )).parse_next(s)?;
-    println!("{:?}", inner);
+enum Run<'s> {
+    Text(&'s str),
+    Char(char),
+    Raw(&'s str),
+    Comment(Spanned<&'s str>),
+}
 
-    let _ = "</".parse_next(s)?;
-    let _ = name.as_str().parse_next(s)?;
-    let _ = ">".parse_next(s)?;
+/// Content as it is built up run by run
+#[derive(Default)]
+struct Text<'s> {
+    value: Cow<'s, str>,
+    /// Whether whitespace was dropped since the last character kept, which becomes a space before the next one
+    space: bool,
+}
+
+impl<'s> Text<'s> {
+    fn verbatim(&mut self, run: Cow<'s, str>) {
+        if std::mem::take(&mut self.space) {
+            self.value.to_mut().push(' ');
+        }
+        if self.value.is_empty() {
+            self.value = run;
+        } else {
+            self.value.to_mut().push_str(&run);
+        }
+    }
+
+    fn collapse(&mut self, run: &'s str) {
+        let trailing = run.ends_with(char::is_whitespace);
+        let trimmed = run.trim();
+        // Most content is a single line of words, which can be borrowed as it is
+        let single = !trimmed.contains(|c: char| c.is_whitespace() && c != ' ') && !trimmed.contains("  ");
+        if self.value.is_empty() && !self.space && single {
+            self.value = Cow::Borrowed(trimmed);
+            self.space = trailing && !trimmed.is_empty();
+            return;
+        }
+        if run.starts_with(char::is_whitespace) && !self.value.is_empty() {
+            self.space = true;
+        }
+        let mut words = run.split_whitespace().peekable();
+        while let Some(word) = words.next() {
+            self.verbatim(Cow::Borrowed(word));
+            self.space = trailing || words.peek().is_some();
+        }
+    }
+}
 
-    Ok(Tag { name, traits, attributes, inner })
+/// `<![CDATA[like so]]>`, giving back the text inside as it was written, `<` and all
+pub fn raw<'s>(s: &mut Input<'s>) -> Result<&'s str> {
+    let _ = "<![CDATA[".parse_next(s)?;
+    let text = cut_err(take_until(0.., "]]>")).context("`]]>` to close the raw block").parse_next(s)?;
+    let _ = "]]>".parse_next(s)?;
+    Ok(text)
 }
 
-fn many<'s>(s: &mut &'s str) -> Result<Vec<Tag>> {
-    repeat(1.., single).fold(Vec::new, |mut acc: Vec<Tag>, item| {
-        acc.push(item);
-        acc
-    }).parse_next(s)
+/// `<!-- like so -->`, giving back just the text inside
+pub fn comment<'s>(s: &mut Input<'s>) -> Result<Spanned<&'s str>> {
+    let _ = "<!--".parse_next(s)?;
+    let text = cut_err(spanned(take_until(0.., "-->"))).context("`-->` to close the comment").parse_next(s)?;
+    let _ = "-->".parse_next(s)?;
+    Ok(text)
 }
 
-fn content<'s>(s: &mut &'s str) -> Result<&'s str> {
-    take_till(0.., '<').parse_next(s)
+/// Whatever broke the inner of a tag: a run of content after children, or a tag after content
+fn mixed<'s>(s: &mut Input<'s>) -> Result<&'s str> {
+    peek(alt((
+        (take_until(1.., ">"), ">").take(),
+        take_till(1.., '<'),
+    ))).parse_next(s)
 }
 
-impl<'a> FromStr for Tag {
-    type Err = ContextError;
+impl FromStr for Tag {
+    type Err = SyntaxError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        single(&mut s.clone())
+        let source = Source::new(s);
+        let mut input = source.input();
+        let tag = single(&mut input).and_then(|tag| end(&mut input).map(|()| tag));
+        tag.map_err(error::finish)
     }
 }
 
@@ -102,38 +346,99 @@ mod test {
     use indexmap::IndexSet;
     use winnow::Parser;
 
+    use crate::parser::{span::{Source, Spanned}, values::Value};
     use crate::parser::tags::single;
 
     use super::content;
     use super::Inner::*;
-    use super::Tag;
+    use super::{Node, Tag};
 
     use super::many;
 
     #[test]
     fn test_parse_tag() {
-        let expected = Tag { name: "button".into(), traits: IndexSet::new(), attributes: IndexMap::new(), inner: Content("".into()) };
+        let expected = Tag { name: "button".into(), traits: IndexSet::new(), attributes: IndexMap::new(), inner: Content("".into()), comments: Vec::new(), span: Default::default() };
         assert_eq!(Ok(expected), "<button></button>".parse());
     }
 
     #[test]
     fn test_parse_short_tag() {
-        let expected = Tag { name: "button".into(), traits: IndexSet::new(), attributes: IndexMap::new(), inner: None };
+        let expected = Tag { name: "button".into(), traits: IndexSet::new(), attributes: IndexMap::new(), inner: None, comments: Vec::new(), span: Default::default() };
         assert_eq!(Ok(expected), "<button />".parse());
     }
 
     #[test]
     fn test_parse_children() {
-        let parsed = many.parse_next(&mut "<foo /><bar />");
-        let expected = vec![Tag::new("foo"), Tag::new("bar")];
+        let parsed = many.parse_next(&mut Source::new("<foo /><bar />").input());
+        let expected: Vec<Node> = vec![Tag::new("foo").into(), Tag::new("bar").into()];
         assert_eq!(expected, parsed.unwrap())
     }
 
     #[test]
     fn test_content() {
-        let parsed = content.parse_next(&mut "Banana<");
+        let source = Source::new("Banana<");
+        let parsed = content(false).parse_next(&mut source.input());
         let expected = "Banana";
-        assert_eq!(expected, parsed.unwrap())
+        assert_eq!(expected, parsed.unwrap().0)
+    }
+
+    #[test]
+    fn test_content_entities() {
+        let mut expected = Tag::new("foo");
+        expected.content("a <b> & c");
+        assert_eq!(Ok(expected), "<foo>a &lt;b&gt; &amp; c</foo>".parse());
+        assert!("<foo>a & c</foo>".parse::<Tag>().is_err());
+    }
+
+    #[test]
+    fn test_whitespace() {
+        for (input, expected) in [
+            ("<foo>\n    Bananas\n</foo>", "Bananas"),
+            ("<foo>  a \n\t b  </foo>", "a b"),
+            ("<foo> a&#32;&#32;b&#xA; </foo>", "a  b\n"),
+            ("<foo preserve>\n  a  b\n</foo>", "\n  a  b\n"),
+            ("<code><![CDATA[if a < b { x }]]></code>", "if a < b { x }"),
+            ("<code> x <![CDATA[ < ]]> y </code>", "x  <  y"),
+            ("<code preserve>\n<![CDATA[<b>&amp;]]>\n</code>", "\n<b>&amp;\n"),
+        ] {
+            let tag: Tag = input.parse().unwrap();
+            assert_eq!(Content(expected.into()), tag.inner, "{input}");
+        }
+        assert!("<foo><![CDATA[x</foo>".parse::<Tag>().is_err());
+
+        let source = Source::new("<foo> Count: <!-- n --> {n} <!-- end --> </foo>").keeping_comments();
+        let tag = single.parse_next(&mut source.input()).unwrap();
+        assert_eq!(Content("Count: {n}".into()), tag.inner);
+        assert_eq!(vec![7, 10], tag.comments.iter().map(|(offset, _)| *offset).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_comments() {
+        let mut expected = Tag::new("foo");
+        expected.children(vec![Tag::new("bar"), Tag::new("baz")]);
+        assert_eq!(Ok(expected), "<foo>\n  <!-- <bar> --> <bar />\n  <!-- -->\n  <baz />\n  <!-- end -->\n</foo>".parse());
+
+        let mut expected = Tag::new("foo");
+        expected.content("Banana");
+        assert_eq!(Ok(expected), "<foo>Ba<!-- <i> -->na<!---->na</foo>".parse());
+
+        let mut expected = Tag::new("foo");
+        expected.content("");
+        assert_eq!(Ok(expected), "<foo><!-- not a child --></foo>".parse());
+    }
+
+    #[test]
+    fn test_keep_comments() {
+        let source = Source::new("<foo><!-- a --><bar /></foo>").keeping_comments();
+        let parsed = single.parse_next(&mut source.input()).unwrap();
+        let Children(children) = parsed.inner else { panic!("Expected children") };
+        assert_eq!(vec![Node::Comment(" a ".into()), Tag::new("bar").into()], children);
+        assert_eq!(9..12, children[0].span().bytes());
+
+        let source = Source::new("<foo>Ba<!-- a -->nana</foo>").keeping_comments();
+        let parsed = single.parse_next(&mut source.input()).unwrap();
+        assert_eq!(Content("Banana".into()), parsed.inner);
+        assert_eq!(vec![(2, Spanned::from(" a "))], parsed.comments);
     }
 
     #[test]
@@ -144,9 +449,19 @@ mod test {
         assert_eq!(expected, parsed.unwrap())
     }
 
+    #[test]
+    fn test_parse_identifiers() {
+        let parsed = "<ui:my-row hidden-when-empty aria-label=\"Row\"><ui:cell /></ui:my-row>".parse();
+        let mut expected = Tag::new("ui:my-row");
+        expected.set("hidden-when-empty").with("aria-label", Value::String("Row".into())).children(vec![Tag::new("ui:cell")]);
+        assert_eq!(expected, parsed.unwrap());
+        assert!("<ui:row></ui:column>".parse::<Tag>().is_err());
+    }
+
     #[test]
     fn test_parse_tag_content() {
-        let mut input = "<foo>Bananas</foo>";
+        let source = Source::new("<foo>Bananas</foo>");
+        let mut input = source.input();
         let parsed = single.parse_next(&mut input);
         let mut expected = Tag::new("foo");
         expected.content("Bananas");
@@ -154,6 +469,41 @@ mod test {
         assert_eq!(expected, parsed.unwrap())
     }
 
+    #[test]
+    fn test_parse_spans() {
+        let source = Source::new("<foo a=1>\n  <bar /></foo>");
+        let tag = single.parse_next(&mut source.input()).unwrap();
+        assert_eq!(0..25, tag.span.bytes());
+        assert_eq!(1..4, tag.name.span.bytes());
+        assert_eq!(7..8, tag.attributes[0].span.bytes());
+        let Children(children) = &tag.inner else { panic!("Expected children") };
+        assert_eq!((2, 3), (children[0].span().start.line, children[0].span().start.column));
+        assert_eq!(12..19, children[0].span().bytes());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let tag: Tag = "<button default label=\"+\" size=12lp color=#f00 range=0..=2 onclick=select(1)><icon /></button>".parse().unwrap();
+        let json = serde_json::to_string(&tag).unwrap();
+        let expected = concat!(
+            r#"{"name":"button","traits":["default"],"attributes":{"label":{"string":"+"},"size":{"length":{"lp":12.0}},"#,
+            r#""color":{"color":{"r":255,"g":0,"b":0,"a":255}},"range":{"range":{"start":0,"end":2,"inclusive":true}},"#,
+            r#""onclick":{"expr":{"call":{"function":["select"],"args":[{"literal":{"int":1}}]}}}},"#,
+            r#""inner":{"children":[{"tag":{"name":"icon","traits":[],"attributes":{},"inner":"none"}}]}}"#,
+        );
+        assert_eq!(expected, json);
+        assert_eq!(tag, serde_json::from_str(&json).unwrap());
+
+        // Order is kept both ways, rather than sorted
+        let tag: Tag = "<box z y x c=1 b=2 a=3>Text</box>".parse().unwrap();
+        let json = serde_json::to_string(&tag).unwrap();
+        assert!(json.contains(r#"["z","y","x"],"attributes":{"c":"#));
+        let parsed: Tag = serde_json::from_str(&json).unwrap();
+        assert_eq!(vec!["c", "b", "a"], parsed.attributes.keys().map(|k| k.as_str()).collect::<Vec<_>>());
+        assert_eq!(vec!["z", "y", "x"], parsed.traits.iter().map(|k| k.as_str()).collect::<Vec<_>>());
+    }
+
     #[test]
     fn test_parse_err() {
         let parsed = "<button".parse::<Tag>();
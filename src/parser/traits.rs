@@ -1,19 +1,26 @@
 use indexmap::IndexSet;
-use winnow::{ascii::{alphanumeric1, space0}, combinator::{not, repeat}, Result, Parser};
+use winnow::{ascii::multispace0, combinator::{not, repeat}, Parser};
 
-pub fn single<'s>(s: &mut &'s str) -> Result<&'s str> {
-    let _ = space0.parse_next(s)?;
-    let key = alphanumeric1.parse_next(s)?;
+use super::{error::{Result, SyntaxError}, ident::identifier, span::{spanned, Input, Spanned}};
+
+pub fn single<'s>(s: &mut Input<'s>) -> Result<Spanned<&'s str>> {
+    let _ = multispace0.parse_next(s)?;
+    let key = spanned(identifier).parse_next(s)?;
     // Do not greedily consume attribute key
-    let _ = not("=").parse_next(s)?;
+    not("=").parse_next(s)?;
     Ok(key)
 }
 
-pub fn many<'s>(s: &mut &'s str) -> Result<IndexSet<String>> {
-    repeat(0.., single).fold(IndexSet::new, |mut acc: IndexSet<_>, item| {
-        acc.insert(item.into());
-        acc
-    }).parse_next(s)
+pub fn many(s: &mut Input) -> Result<IndexSet<Spanned<String>>> {
+    let items: Vec<_> = repeat(0.., single).parse_next(s)?;
+    let mut traits: IndexSet<Spanned<String>> = IndexSet::with_capacity(items.len());
+    for item in items {
+        if let Some(first) = traits.get(item.value) {
+            return Err(SyntaxError::DuplicatePreset { name: item.value.into(), first: first.span, second: item.span }.cut());
+        }
+        traits.insert(item.map(String::from));
+    }
+    Ok(traits)
 }
 
 #[cfg(test)]
@@ -21,30 +28,36 @@ mod test {
     use indexmap::IndexSet;
     use winnow::Parser;
 
+    use crate::parser::span::{Source, Spanned};
+
     use super::many;
 
     use super::single;
 
     #[test]
     fn test_parse_trait() {
-        let mut input = "foo";
+        let source = Source::new("foo");
+        let mut input = source.input();
         let parsed = single.parse_next(&mut input);
-        assert_eq!(input, "");
+        assert_eq!(**input, "");
         assert!(parsed.is_ok());
-        assert_eq!(parsed.unwrap(), "foo");
+        assert_eq!(*parsed.unwrap(), "foo");
     }
 
     #[test]
     fn test_parse_traits() {
-        let mut input = "foo bo";
+        let source = Source::new("foo bo");
+        let mut input = source.input();
         let parsed = many.parse_next(&mut input);
-        assert_eq!(input, "");
+        assert_eq!(**input, "");
         assert!(parsed.is_ok());
 
-        let mut expected: IndexSet<String> = IndexSet::new();
+        let mut expected: IndexSet<Spanned<String>> = IndexSet::new();
         expected.insert("foo".into());
         expected.insert("bo".into());
 
-        assert_eq!(expected, parsed.unwrap());
+        let actual = parsed.unwrap();
+        assert_eq!(expected, actual);
+        assert_eq!(4..6, actual[1].span.bytes());
     }
-}
\ No newline at end of file
+}
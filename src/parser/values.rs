@@ -1,93 +1,652 @@
-use std::ops::Range;
+use std::{borrow::Cow, fmt, ops::{self, Bound, RangeBounds}};
 
-use winnow::{ascii::{digit0, digit1}, combinator::alt, token::take_till, Result, Parser};
+use indexmap::IndexMap;
+use winnow::{ascii::multispace0, combinator::{alt, cut_err, not, opt, repeat, separated, terminated}, error::ErrMode, token::{one_of, take_till, take_while}, Parser};
 
-fn string(s: &mut &str) -> Result<String> {
-    let _ = '"'.parse_next(s)?;
-    let result = take_till(0.., ['"']).parse_next(s)?;
+use super::{color::{color, Color}, error::{Result, SyntaxError}, escape, expr::{expression, Expr}, ident, length::{length, Length}, span::{spanned, Input, Spanned}};
+
+/// Only owned when escapes had to be replaced
+pub(crate) fn string<'s>(s: &mut Input<'s>) -> Result<Cow<'s, str>> {
     let _ = '"'.parse_next(s)?;
-    Ok(result.into())
+    let result = repeat(0.., alt((
+        take_till(1.., ['"', '\\']).map(Cow::Borrowed),
+        escape::backslash.map(|c| Cow::Owned(c.into())),
+    ))).fold(|| Cow::Borrowed(""), |acc: Cow<'s, str>, piece: Cow<'s, str>| {
+        if acc.is_empty() {
+            return piece;
+        }
+        let mut acc = acc.into_owned();
+        acc.push_str(&piece);
+        Cow::Owned(acc)
+    }).parse_next(s)?;
+    let _ = cut_err('"').context("closing `\"`").parse_next(s)?;
+    Ok(result)
 }
 
-fn int(s: &mut &str) -> Result<i64> {
-    let result = digit1.parse_next(s)?;
-    Ok(result.parse::<i64>().expect("Should only be digits"))
+/// A number as written, before it is decided whether it fits
+struct Numeral<'s> {
+    negative: bool,
+    radix: u32,
+    /// Still including any `_` separators
+    digits: &'s str,
+    float: bool,
 }
 
-fn float(s: &mut &str) -> Result<f64> {
-    let left = digit0.parse_next(s)?;
-    let _ = ".".parse_next(s)?;
-    let right = digit1.parse_next(s)?;
-    Ok(format!("{left}.{right}").parse::<f64>().expect("Should be digits"))
+impl Numeral<'_> {
+    fn int(&self) -> Option<i64> {
+        let magnitude = u64::from_str_radix(&self.digits.replace('_', ""), self.radix).ok()?;
+        let value = if self.negative { -i128::from(magnitude) } else { i128::from(magnitude) };
+        i64::try_from(value).ok()
+    }
+
+    fn float(&self) -> Option<f64> {
+        if !self.float {
+            return self.int().map(|i| i as f64);
+        }
+        let value = self.digits.replace('_', "").parse::<f64>().ok().filter(|f| f.is_finite())?;
+        Some(if self.negative { -value } else { value })
+    }
 }
 
-fn bool(s: &mut &str) -> Result<bool> {
-    let result = alt(["true", "false"]).parse_next(s)?;
-    match result {
-        "true" => Ok(true),
-        "false" => Ok(false),
-        &_ => panic!("Result can only be true or false")
+/// Digits in the given radix, which may be separated by `_` after the first
+fn digits<'s>(radix: u32) -> impl Parser<Input<'s>, &'s str, ErrMode<SyntaxError>> {
+    (
+        one_of(move |c: char| c.is_digit(radix)),
+        take_while(0.., move |c: char| c.is_digit(radix) || c == '_'),
+    ).take()
+}
+
+/// Signed decimal, or `0x`, `0o` and `0b` integers, and decimal floats with an optional exponent
+fn numeral<'s>(s: &mut Input<'s>) -> Result<Numeral<'s>> {
+    let negative = opt(one_of(['-', '+'])).parse_next(s)? == Some('-');
+    let prefix = opt(alt(("0x".value(16), "0o".value(8), "0b".value(2)))).parse_next(s)?;
+    if let Some(radix) = prefix {
+        let digits = cut_err(digits(radix)).context("digits after the radix prefix").parse_next(s)?;
+        return Ok(Numeral { negative, radix, digits, float: false });
     }
+    let (float, digits) = alt((
+        (opt(digits(10)), '.', digits(10), opt(exponent)).value(true),
+        (digits(10), opt(exponent)).map(|(_, e)| e.is_some()),
+    )).with_taken().parse_next(s)?;
+    Ok(Numeral { negative, radix: 10, digits, float })
+}
+
+fn exponent<'s>(s: &mut Input<'s>) -> Result<&'s str> {
+    (one_of(['e', 'E']), opt(one_of(['+', '-'])), digits(10)).take().parse_next(s)
+}
+
+fn int(s: &mut Input) -> Result<i64> {
+    let numeral = spanned(numeral).verify(|n| !n.float).parse_next(s)?;
+    numeral.int().ok_or_else(|| SyntaxError::NumberOutOfRange { span: numeral.span }.cut())
 }
 
-fn range(s: &mut &str) -> Result<Range<i64>> {
-    let start = int.parse_next(s)?;
+fn float(s: &mut Input) -> Result<f64> {
+    let numeral = spanned(numeral).verify(|n| n.float).parse_next(s)?;
+    numeral.float().ok_or_else(|| SyntaxError::NumberOutOfRange { span: numeral.span }.cut())
+}
+
+/// Any number as a float, for values such as color channels that do not care how it was written
+pub(crate) fn number(s: &mut Input) -> Result<f64> {
+    let numeral = spanned(numeral).parse_next(s)?;
+    numeral.float().ok_or_else(|| SyntaxError::NumberOutOfRange { span: numeral.span }.cut())
+}
+
+/// Only a whole word, so names such as `false_alarm` are left to expressions
+fn bool(s: &mut Input) -> Result<bool> {
+    terminated(alt(("true".value(true), "false".value(false))), not(one_of(ident::is_continue))).parse_next(s)
+}
+
+/// Either bound of a range may be left out, and if either is a float then so is the whole range
+fn range(s: &mut Input) -> Result<Value> {
+    let start = opt(spanned(numeral)).parse_next(s)?;
     let _ = "..".parse_next(s)?;
-    let end = int.parse_next(s)?;
-    Ok(start..end)
+    let inclusive = opt('=').parse_next(s)?.is_some();
+    let end = if inclusive {
+        Some(cut_err(spanned(numeral)).context("end of the inclusive range").parse_next(s)?)
+    } else {
+        opt(spanned(numeral)).parse_next(s)?
+    };
+
+    let float = [&start, &end].into_iter().flatten().any(|n| n.float);
+    if float {
+        Ok(Value::FloatRange(Range { start: bound(start, Numeral::float)?, end: bound(end, Numeral::float)?, inclusive }))
+    } else {
+        Ok(Value::Range(Range { start: bound(start, Numeral::int)?, end: bound(end, Numeral::int)?, inclusive }))
+    }
+}
+
+/// Converts a bound of a range, blaming it if it does not fit
+fn bound<'s, T>(numeral: Option<Spanned<Numeral<'s>>>, convert: impl Fn(&Numeral<'s>) -> Option<T>) -> Result<Option<T>> {
+    numeral.map(|n| convert(&n).ok_or_else(|| SyntaxError::NumberOutOfRange { span: n.span }.cut())).transpose()
+}
+
+/// Like the std range types rolled into one, as markup can write any of them
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Range<T> {
+    pub start: Option<T>,
+    pub end: Option<T>,
+    /// Whether `end` is part of the range, as with `a..=b`
+    pub inclusive: bool,
+}
+
+impl<T: Copy> Range<T> {
+    /// `a..b`
+    pub fn as_range(&self) -> Option<ops::Range<T>> {
+        match (self.start, self.end, self.inclusive) {
+            (Some(start), Some(end), false) => Some(start..end),
+            _ => None,
+        }
+    }
+
+    /// `a..=b`
+    pub fn as_range_inclusive(&self) -> Option<ops::RangeInclusive<T>> {
+        match (self.start, self.end, self.inclusive) {
+            (Some(start), Some(end), true) => Some(start..=end),
+            _ => None,
+        }
+    }
+
+    /// `a..`
+    pub fn as_range_from(&self) -> Option<ops::RangeFrom<T>> {
+        match (self.start, self.end) {
+            (Some(start), None) => Some(start..),
+            _ => None,
+        }
+    }
+
+    /// `..b`
+    pub fn as_range_to(&self) -> Option<ops::RangeTo<T>> {
+        match (self.start, self.end, self.inclusive) {
+            (None, Some(end), false) => Some(..end),
+            _ => None,
+        }
+    }
+
+    /// `..=b`
+    pub fn as_range_to_inclusive(&self) -> Option<ops::RangeToInclusive<T>> {
+        match (self.start, self.end, self.inclusive) {
+            (None, Some(end), true) => Some(..=end),
+            _ => None,
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Display for Range<T> {
+    /// Bounds use `Debug` so that floats keep their `.0`, and are not read back as integers
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(start) = &self.start {
+            write!(f, "{start:?}")?;
+        }
+        write!(f, "..{}", if self.inclusive { "=" } else { "" })?;
+        if let Some(end) = &self.end {
+            write!(f, "{end:?}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<T> RangeBounds<T> for Range<T> {
+    fn start_bound(&self) -> Bound<&T> {
+        self.start.as_ref().map_or(Bound::Unbounded, Bound::Included)
+    }
+
+    fn end_bound(&self) -> Bound<&T> {
+        match &self.end {
+            None => Bound::Unbounded,
+            Some(end) if self.inclusive => Bound::Included(end),
+            Some(end) => Bound::Excluded(end),
+        }
+    }
+}
+
+impl<T> From<ops::Range<T>> for Range<T> {
+    fn from(range: ops::Range<T>) -> Self {
+        Range { start: Some(range.start), end: Some(range.end), inclusive: false }
+    }
+}
+
+impl<T> From<ops::RangeInclusive<T>> for Range<T> {
+    fn from(range: ops::RangeInclusive<T>) -> Self {
+        let (start, end) = range.into_inner();
+        Range { start: Some(start), end: Some(end), inclusive: true }
+    }
+}
+
+impl<T> From<ops::RangeFrom<T>> for Range<T> {
+    fn from(range: ops::RangeFrom<T>) -> Self {
+        Range { start: Some(range.start), end: None, inclusive: false }
+    }
+}
+
+impl<T> From<ops::RangeTo<T>> for Range<T> {
+    fn from(range: ops::RangeTo<T>) -> Self {
+        Range { start: None, end: Some(range.end), inclusive: false }
+    }
+}
+
+impl<T> From<ops::RangeToInclusive<T>> for Range<T> {
+    fn from(range: ops::RangeToInclusive<T>) -> Self {
+        Range { start: None, end: Some(range.end), inclusive: true }
+    }
+}
+
+impl<T> From<ops::RangeFull> for Range<T> {
+    fn from(_: ops::RangeFull) -> Self {
+        Range { start: None, end: None, inclusive: false }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
 pub enum Value {
     String(String),
     Int(i64),
     Float(f64),
     Bool(bool),
     Range(Range<i64>),
+    FloatRange(Range<f64>),
+    Color(Color),
+    Length(Length),
+    List(Vec<Spanned<Value>>),
+    Map(IndexMap<Spanned<String>, Spanned<Value>>),
+    /// Bound to state at runtime, see [`expr`](super::expr)
+    Expr(Box<Expr>),
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        if let Value::String(s) = self { Some(s) } else { None }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        if let Value::Int(i) = self { Some(*i) } else { None }
+    }
+
+    /// Integers are floats too, so `1` will do where `1.0` is expected
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Value::Float(f) => Some(*f),
+            Value::Int(i) => Some(*i as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        if let Value::Bool(b) = self { Some(*b) } else { None }
+    }
+
+    pub fn as_color(&self) -> Option<Color> {
+        if let Value::Color(c) = self { Some(*c) } else { None }
+    }
+
+    pub fn as_length(&self) -> Option<Length> {
+        if let Value::Length(l) = self { Some(*l) } else { None }
+    }
+
+    pub fn as_list(&self) -> Option<&[Spanned<Value>]> {
+        if let Value::List(items) = self { Some(items) } else { None }
+    }
+
+    pub fn as_expr(&self) -> Option<&Expr> {
+        if let Value::Expr(e) = self { Some(e.as_ref()) } else { None }
+    }
+
+    pub fn as_map(&self) -> Option<&IndexMap<Spanned<String>, Spanned<Value>>> {
+        if let Value::Map(entries) = self { Some(entries) } else { None }
+    }
+
+    /// What kind of value this is, for errors such as "expected a color, found an integer"
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Value::String(_) => "a string",
+            Value::Int(_) => "an integer",
+            Value::Float(_) => "a float",
+            Value::Bool(_) => "a boolean",
+            Value::Range(_) => "an integer range",
+            Value::FloatRange(_) => "a float range",
+            Value::Color(_) => "a color",
+            Value::Length(_) => "a length",
+            Value::List(_) => "a list",
+            Value::Map(_) => "a map",
+            Value::Expr(_) => "an expression",
+        }
+    }
+}
+
+/// Types an attribute value can be taken as
+pub trait FromValue: Sized {
+    /// The kind of value expected, in the same words as [`Value::kind`]
+    const EXPECTED: &'static str;
+
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+impl FromValue for Value {
+    const EXPECTED: &'static str = "a value";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+impl FromValue for String {
+    const EXPECTED: &'static str = "a string";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        value.as_str().map(String::from)
+    }
+}
+
+impl FromValue for i64 {
+    const EXPECTED: &'static str = "an integer";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        value.as_int()
+    }
+}
+
+impl FromValue for f64 {
+    const EXPECTED: &'static str = "a number";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        value.as_float()
+    }
+}
+
+impl FromValue for f32 {
+    const EXPECTED: &'static str = "a number";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        value.as_float().map(|f| f as f32)
+    }
 }
 
-pub fn value(s: &mut &str) -> Result<Value> {
+impl FromValue for bool {
+    const EXPECTED: &'static str = "a boolean";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        value.as_bool()
+    }
+}
+
+impl FromValue for Range<i64> {
+    const EXPECTED: &'static str = "an integer range";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        if let Value::Range(range) = value { Some(*range) } else { None }
+    }
+}
+
+/// Like floats, integer ranges will do where a float range is expected
+impl FromValue for Range<f64> {
+    const EXPECTED: &'static str = "a range";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::FloatRange(range) => Some(*range),
+            Value::Range(range) => Some(Range { start: range.start.map(|s| s as f64), end: range.end.map(|e| e as f64), inclusive: range.inclusive }),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for Color {
+    const EXPECTED: &'static str = "a color";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        value.as_color()
+    }
+}
+
+impl FromValue for Length {
+    const EXPECTED: &'static str = "a length";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        value.as_length()
+    }
+}
+
+impl FromValue for Expr {
+    const EXPECTED: &'static str = "an expression";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        value.as_expr().cloned()
+    }
+}
+
+/// Writes the value back as markup, which parses to an equal value
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::String(s) => write!(f, "\"{}\"", escape::string(s)),
+            Value::Int(i) => write!(f, "{i}"),
+            Value::Float(x) => write!(f, "{x:?}"),
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::Range(range) => write!(f, "{range}"),
+            Value::FloatRange(range) => write!(f, "{range}"),
+            Value::Color(color) => write!(f, "{color}"),
+            Value::Length(length) => write!(f, "{length}"),
+            Value::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    write!(f, "{}{}", if i == 0 { "" } else { ", " }, item.value)?;
+                }
+                write!(f, "]")
+            }
+            Value::Map(entries) if entries.is_empty() => write!(f, "[:]"),
+            Value::Map(entries) => {
+                write!(f, "[")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    write!(f, "{}{}: {}", if i == 0 { "" } else { ", " }, key.value, value.value)?;
+                }
+                write!(f, "]")
+            }
+            Value::Expr(expr) if matches!(**expr, Expr::Call { .. }) => write!(f, "{expr}"),
+            Value::Expr(expr) => write!(f, "{{{expr}}}"),
+        }
+    }
+}
+
+/// `[a, b]` lists, or `[key: a]` maps with `[:]` when empty. Either may nest and end with a `,`
+fn compound(s: &mut Input) -> Result<Value> {
+    let _ = ('[', multispace0).parse_next(s)?;
+    let separator = || (multispace0, ',', multispace0);
+    let entry = (spanned(ident::name.map(String::from)), multispace0, ':', multispace0, spanned(value))
+        .map(|(key, _, _, _, value)| (key, value));
+    let result = alt((
+        ':'.value(Value::Map(IndexMap::new())),
+        separated(1.., entry, separator()).map(|entries: Vec<_>| Value::Map(entries.into_iter().collect())),
+        separated(0.., spanned(value), separator()).map(Value::List),
+    )).parse_next(s)?;
+    let _ = opt(separator()).parse_next(s)?;
+    let _ = cut_err((multispace0, ']')).context("`,` or `]`").parse_next(s)?;
+    Ok(result)
+}
+
+pub fn value(s: &mut Input) -> Result<Value> {
     alt((
-        string.map(|s| Value::String(s)),
-        range.map(|r| Value::Range(r)),
-        float.map(|f| Value::Float(f)),
-        int.map(|i| Value::Int(i)),
-        bool.map(|b| Value::Bool(b))))
+        string.map(|s| Value::String(s.into_owned())),
+        compound,
+        range,
+        length.map(Value::Length),
+        float.map(Value::Float),
+        int.map(Value::Int),
+        bool.map(Value::Bool),
+        color.map(Value::Color),
+        expression.map(|e| Value::Expr(Box::new(e)))))
     .parse_next(s)
 }
 
 #[cfg(test)]
 mod test {
     use winnow::Parser;
-    use super::{Value, value};
+    use crate::parser::{color::Color, error::SyntaxError, expr::Expr, length::Length, span::Source};
+    use std::ops::RangeBounds;
+
+    use super::{Range, Value, value};
 
     #[test]
     fn test_string() {
         let expected = Value::String("foo".into());
-        assert_eq!(expected, value.parse_next(&mut "\"foo\"").unwrap());
+        assert_eq!(expected, value.parse_next(&mut Source::new("\"foo\"").input()).unwrap());
     }
 
     #[test]
     fn test_int() {
         let expected = Value::Int(42);
-        assert_eq!(expected, value.parse_next(&mut "42").unwrap());
+        assert_eq!(expected, value.parse_next(&mut Source::new("42").input()).unwrap());
     }
 
     #[test]
     fn test_float() {
         let expected = Value::Float(3.14);
-        assert_eq!(expected, value.parse_next(&mut "3.14").unwrap());
+        assert_eq!(expected, value.parse_next(&mut Source::new("3.14").input()).unwrap());
     }
 
     #[test]
     fn test_bool() {
         let expected = Value::Bool(true);
-        assert_eq!(expected, value.parse_next(&mut "true").unwrap());
+        assert_eq!(expected, value.parse_next(&mut Source::new("true").input()).unwrap());
+        assert_eq!(Value::Bool(false), value.parse_next(&mut Source::new("false]").input()).unwrap());
+        // Names that only start with `true` or `false` are not booleans
+        assert!(matches!(value.parse_next(&mut Source::new("false_alarm()").input()).unwrap().as_expr(), Some(Expr::Call { .. })));
+        assert!(value.parse_next(&mut Source::new("true_x").input()).is_err());
+        assert!(matches!(value.parse_next(&mut Source::new("{true_x}").input()).unwrap().as_expr(), Some(Expr::Ref(_))));
+    }
+
+    #[test]
+    fn test_length() {
+        let expected = Value::Length(Length::Percent(50.0));
+        assert_eq!(expected, value.parse_next(&mut Source::new("50%").input()).unwrap());
+        let expected = Value::Length(Length::Lp(0.5));
+        assert_eq!(expected, value.parse_next(&mut Source::new("0.5lp").input()).unwrap());
+    }
+
+    #[test]
+    fn test_expr() {
+        let parse = |input| value.parse_next(&mut Source::new(input).input()).unwrap();
+        assert!(matches!(parse("{counter}").as_expr(), Some(Expr::Ref(_))));
+        assert!(matches!(parse("increment()").as_expr(), Some(Expr::Call { .. })));
+        // Colors are tried first, so `rgb()` is never a call
+        assert!(matches!(parse("rgb(0, 0, 0)"), Value::Color(_)));
+        // But named colors are only names, so `green()` is a call
+        assert!(matches!(parse("green()").as_expr(), Some(Expr::Call { .. })));
+        assert_eq!("{user.name}", parse("{user.name}").to_string());
+    }
+
+    #[test]
+    fn test_color() {
+        let expected = Value::Color(Color::rgb(255, 0, 0));
+        for input in ["#f00", "rgb(255, 0, 0)", "red"] {
+            assert_eq!(expected, value.parse_next(&mut Source::new(input).input()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_escaped_string() {
+        let expected = Value::String("say \"hi\"\n".into());
+        assert_eq!(expected, value.parse_next(&mut Source::new("\"say \\\"hi\\\"\\n\"").input()).unwrap());
+    }
+
+    #[test]
+    fn test_numbers() {
+        let cases = [
+            ("-10", Value::Int(-10)),
+            ("+7", Value::Int(7)),
+            ("1_000_000", Value::Int(1_000_000)),
+            ("0xFF", Value::Int(255)),
+            ("-0x10", Value::Int(-16)),
+            ("0b1010", Value::Int(10)),
+            ("0o17", Value::Int(15)),
+            ("-9223372036854775808", Value::Int(i64::MIN)),
+            ("1e-3", Value::Float(1e-3)),
+            ("-2.5E2", Value::Float(-250.0)),
+            (".5", Value::Float(0.5)),
+            ("1_000.25", Value::Float(1000.25)),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(expected, value.parse_next(&mut Source::new(input).input()).unwrap(), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_number_out_of_range() {
+        for input in ["9223372036854775808", "0x1_0000_0000_0000_0000", "1e400"] {
+            let source = Source::new(input);
+            let error = value.parse_next(&mut source.input()).unwrap_err().into_inner().unwrap();
+            assert_eq!(SyntaxError::NumberOutOfRange { span: source.span(0..input.len()) }, error);
+        }
     }
 
     #[test]
     fn test_range() {
-        let expected = Value::Range(0..42);
-        assert_eq!(expected, value.parse_next(&mut "0..42").unwrap());
+        let expected = Value::Range((0..42).into());
+        assert_eq!(expected, value.parse_next(&mut Source::new("0..42").input()).unwrap());
+    }
+
+    #[test]
+    fn test_ranges() {
+        let cases = [
+            ("-5..=5", Value::Range((-5..=5).into())),
+            ("..10", Value::Range((..10).into())),
+            ("..=10", Value::Range((..=10).into())),
+            ("5..", Value::Range((5..).into())),
+            ("..", Value::Range((..).into())),
+            ("0.0..1.0", Value::FloatRange((0.0..1.0).into())),
+            ("0..=.5", Value::FloatRange((0.0..=0.5).into())),
+            ("1.5..", Value::FloatRange((1.5..).into())),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(expected, value.parse_next(&mut Source::new(input).input()).unwrap(), "{input}");
+        }
+        assert!(value.parse_next(&mut Source::new("1..=").input()).is_err());
+    }
+
+    #[test]
+    fn test_range_accessors() {
+        let range: Range<i64> = (2..=4).into();
+        assert_eq!(Some(2..=4), range.as_range_inclusive());
+        assert_eq!(None, range.as_range());
+        assert!(range.contains(&4));
+        let range: Range<usize> = (1..).into();
+        assert_eq!([1, 2, 3], [0, 1, 2, 3][range.as_range_from().unwrap()]);
+        let range: Range<i64> = (..3).into();
+        assert_eq!(Some(..3), range.as_range_to());
+        assert_eq!(None, range.as_range_to_inclusive());
+        let range: Range<i64> = (..=3).into();
+        assert_eq!(Some(..=3), range.as_range_to_inclusive());
+        assert_eq!(None, range.as_range_to());
+        assert!(range.contains(&3));
+    }
+
+    #[test]
+    fn test_compound() {
+        let parse = |input| value.parse_next(&mut Source::new(input).input()).unwrap();
+        let list = parse("[4, 8 ,\n 4,8,]");
+        let corners: Vec<_> = list.as_list().unwrap().iter().map(|v| v.as_int().unwrap()).collect();
+        assert_eq!(vec![4, 8, 4, 8], corners);
+        assert_eq!(Some(&[][..]), parse("[]").as_list());
+        assert_eq!(0, parse("[:]").as_map().unwrap().len());
+
+        let stops = parse("[start: [0.0, red], end: [1.0, #00f]]");
+        let end = stops.as_map().unwrap()["end"].as_list().unwrap();
+        assert_eq!(Some(Color::rgb(0, 0, 255)), end[1].as_color());
+
+        assert!(value.parse_next(&mut Source::new("[1 2]").input()).is_err());
+        assert!(value.parse_next(&mut Source::new("[a: 1, 2]").input()).is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        for input in [
+            "\"say \\\"hi\\\"\"", "-42", "0.5", "1e-7", "true", "-3..=4", "..", "0.0..", "#ff000080", "12.0px", "50.0%",
+            "[]", "[:]", "[1, [2.0, \"3\"], [a: red]]", "{counter}", "{ user.name }", "increment( 1 )",
+        ] {
+            let parsed = value.parse_next(&mut Source::new(input).input()).unwrap();
+            let printed = parsed.to_string();
+            assert_eq!(parsed, value.parse_next(&mut Source::new(&printed).input()).unwrap(), "{printed}");
+        }
+        assert_eq!("[1, 2.0, [a: \"b\"]]", value.parse_next(&mut Source::new("[1,2.0,[a:\"b\"]]").input()).unwrap().to_string());
     }
 }
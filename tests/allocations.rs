@@ -0,0 +1,59 @@
+//! Checks that the borrowed tree allocates well under the owned one. This is a binary of its own, so the counting
+//! allocator is only ever installed here, and it runs without the test harness, whose threads would allocate too.
+
+// The parser is only reached through its source, as the crate has no library to link against. Only some of it is used,
+// and its test modules are left with nothing but their imports, as there is no harness to run them.
+#![allow(dead_code, unused_imports)]
+
+use std::{alloc::{GlobalAlloc, Layout, System}, sync::atomic::{AtomicUsize, Ordering}};
+
+#[path = "../src/parser/mod.rs"]
+mod parser;
+
+use parser::{borrowed, span::Source, tags};
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// How many allocations `f` makes, counting what it returns but not dropping it
+fn count<T>(f: impl FnOnce() -> T) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let result = f();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    drop(result);
+    allocations
+}
+
+fn generated(rows: usize) -> String {
+    let mut text = String::from("<column gap=4lp>\n");
+    for i in 0..rows {
+        text += &format!("    <row hidden id={i} label=\"row\" weight=1fr>\n        <text color=red>Row number {i}</text>\n        <button onclick=select({i}) />\n    </row>\n");
+    }
+    text + "</column>"
+}
+
+fn main() {
+    let text = generated(200);
+    let source = Source::new(&text);
+    let owned = count(|| text.parse::<tags::Tag>().unwrap());
+    let borrowed = count(|| borrowed::Tag::parse(&source).unwrap());
+    println!("owned: {owned} allocations, borrowed: {borrowed} allocations");
+    // Only the lists within each tag and values other than strings still allocate, which comes to about half
+    assert!(borrowed * 5 < owned * 3, "{borrowed} borrowed allocations against {owned} owned");
+    assert_eq!(text.parse::<tags::Tag>().unwrap(), borrowed::Tag::parse(&source).unwrap().into_owned());
+}